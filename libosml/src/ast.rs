@@ -0,0 +1,308 @@
+use super::{Error, Line, Pos};
+use std::path::PathBuf;
+
+//  A point in the source, `pos` counts characters rather than bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    pub line: Line,
+    pub pos: Pos,
+}
+
+impl Position {
+    pub fn new(line: Line, pos: Pos) -> Self {
+        Position { line, pos }
+    }
+}
+
+//  Goes from `start` up to, but not including, `end`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    //  Covers the single character at `at`.
+    pub fn at(at: Position) -> Self {
+        Span::new(at, Position::new(at.line, at.pos + 1))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    pub children: Vec<Node>,
+    pub meta: Metadata,
+    //  Every file pulled in by `[include ...]`, nested includes too.
+    pub includes: Vec<PathBuf>,
+    //  Notes that are referenced somewhere, in order of their number.
+    pub footnotes: Vec<Footnote>,
+    //  Every heading in order, see `[toc]`.
+    pub outline: Vec<Heading>,
+    //  Problems that don't stop the document from being rendered, like images without alt text.
+    pub warnings: Vec<Error>,
+}
+
+//  A block rendered as `<h1>` through `<h6>`, `id` is the one it can be linked to by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub level: usize,
+    pub id: String,
+    pub text: String,
+}
+
+//  Filled in by `[meta ...]` blocks, later keys win.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    //  YYYY-MM-DD
+    pub date: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Block(Block),
+    Inline(Inline),
+    List(List),
+    DefinitionList(DefinitionList),
+    Code(Code),
+    Link(Link),
+    Image(Image),
+    Table(Table),
+    Paragraph(Paragraph),
+    FootnoteRef(FootnoteRef),
+    //  An empty line, only left in the tree with `Context::legacy_breaks`.
+    Break(Span),
+    //  Already rendered output, usually left behind by plugins.
+    //  Never escaped, so this is the one way to opt into raw markup.
+    Raw(String),
+}
+
+//  `key=value` pairs in the order they were written, extra classes are joined into one `class`.
+pub type Attributes = Vec<(String, String)>;
+
+impl Node {
+    //  `None` for raw output, which doesn't come from the source.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Block(block) => Some(block.span),
+            Self::Inline(inline) => Some(inline.span()),
+            Self::List(list) => Some(list.span),
+            Self::DefinitionList(list) => Some(list.span),
+            Self::Code(code) => Some(code.span),
+            Self::Link(link) => Some(link.span),
+            Self::Image(image) => Some(image.span),
+            Self::Table(table) => Some(table.span),
+            Self::Paragraph(paragraph) => Some(paragraph.span),
+            Self::FootnoteRef(footnote) => Some(footnote.span),
+            Self::Break(span) => Some(*span),
+            Self::Raw(_) => None,
+        }
+    }
+
+    //  Every list of nodes held by this one, in the order they're written.
+    pub fn children_mut(&mut self) -> Vec<&mut Vec<Node>> {
+        match self {
+            Self::Block(block) => vec![&mut block.children],
+            Self::Paragraph(paragraph) => vec![&mut paragraph.children],
+            Self::Link(link) => vec![&mut link.children],
+            Self::List(list) => list
+                .items
+                .iter_mut()
+                .map(|item| &mut item.children)
+                .collect(),
+            Self::DefinitionList(list) => list
+                .entries
+                .iter_mut()
+                .flat_map(|definition| [&mut definition.term, &mut definition.children])
+                .collect(),
+            Self::Table(table) => table
+                .head
+                .iter_mut()
+                .chain(table.body.iter_mut())
+                .flatten()
+                .map(|cell| &mut cell.children)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+//  Consecutive lines of text, see `group_paragraphs`.
+#[derive(Debug, Clone)]
+pub struct Paragraph {
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+//  `[name ...]`
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub name: String,
+    pub attributes: Attributes,
+    //  Rendered as a `<div>` when `None`, see `Context::elements`.
+    pub element: Option<String>,
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+//  `[code ...]`, `text` is exactly as written.
+#[derive(Debug, Clone)]
+pub struct Code {
+    pub attributes: Attributes,
+    //  From `[code lang=rust ...]`, see `highlight`.
+    pub lang: Option<String>,
+    pub text: String,
+    pub span: Span,
+}
+
+//  `[^id]`, numbered in order of appearance once the whole document is parsed.
+#[derive(Debug, Clone)]
+pub struct FootnoteRef {
+    pub id: String,
+    //  `None` when there's no note called `id`.
+    pub number: Option<usize>,
+    //  Counts the references to the same note, starting at 1, so that each can be linked back to.
+    pub index: usize,
+    pub span: Span,
+}
+
+//  `[footnote id text]`, taken out of where it's written and placed at the end of the document.
+#[derive(Debug, Clone)]
+pub struct Footnote {
+    pub id: String,
+    pub number: usize,
+    pub references: usize,
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+//  `[link href text]`
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub attributes: Attributes,
+    pub href: String,
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+//  `[img src alt]`
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub attributes: Attributes,
+    pub src: String,
+    pub alt: Option<String>,
+    pub span: Span,
+}
+
+//  `[table ...]`, `head` is only filled in when there's a separator row.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub attributes: Attributes,
+    pub head: Vec<Vec<TableCell>>,
+    pub body: Vec<Vec<TableCell>>,
+    //  One per column, columns past the end aren't aligned.
+    pub align: Vec<Align>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableCell {
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+}
+
+#[derive(Debug, Clone)]
+pub enum Inline {
+    Text {
+        text: String,
+        span: Span,
+    },
+    Format {
+        format: Format,
+        children: Vec<Inline>,
+        span: Span,
+    },
+}
+
+impl Inline {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Text { span, .. } | Self::Format { span, .. } => *span,
+        }
+    }
+}
+
+//  `depth` starts at 1 for the outermost list.
+#[derive(Debug, Clone)]
+pub struct List {
+    pub ordered: bool,
+    pub depth: usize,
+    pub items: Vec<ListItem>,
+    pub span: Span,
+}
+
+//  Lists nested under an item are the last of its `children`.
+#[derive(Debug, Clone)]
+pub struct ListItem {
+    //  `Some` for task items like `+ [x] done`, telling whether it's checked.
+    pub checked: Option<bool>,
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+//  Consecutive `: term` lines, each followed by its indented definition.
+#[derive(Debug, Clone)]
+pub struct DefinitionList {
+    pub entries: Vec<Definition>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub term: Vec<Node>,
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+impl Block {
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl List {
+    //  The depth that the last item of this list sits at.
+    pub fn last_depth(&self) -> usize {
+        match self.items.last().and_then(|item| item.children.last()) {
+            Some(Node::List(list)) => list.last_depth(),
+            _ => self.depth,
+        }
+    }
+}
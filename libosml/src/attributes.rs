@@ -0,0 +1,212 @@
+use super::{
+    is_unsafe_url, is_whitespace, Attributes, Error, ErrorType, Line, Parser, Pos, Position,
+    Result, Span,
+};
+
+//  Attributes come straight after a block's name, as any mix of `#id` and `.class` written onto the name,
+//  `{key=value ...}`, and `key=value` words. Text that would look like the latter needs a `\=`.
+//  Expects the character after the name, returns where the block's content starts.
+pub fn parse_attributes(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+) -> Result<(Attributes, Line, Pos)> {
+    let mut attributes = Attributes::new();
+    pos = parse_shorthand(p, line, pos, &mut attributes)?;
+
+    //  An unclosed `{` runs to the end of the document.
+    while let Some(vline) = p.lines.get(line) {
+        let rest = &vline[pos.min(vline.len())..];
+        //  `{{name}}` is a variable at the start of the content instead.
+        if rest.first() == Some(&'{') && rest.get(1) != Some(&'{') {
+            (line, pos) = parse_braces(p, line, pos, &mut attributes)?;
+        } else if rest.get(key_len(rest)) == Some(&'=') && key_len(rest) > 0 {
+            pos = parse_pair(p, line, pos, &mut attributes)?;
+        } else {
+            break;
+        }
+        if p.lines
+            .get(line)
+            .and_then(|vline| vline.get(pos))
+            .is_some_and(|&c| is_whitespace(c))
+        {
+            pos += 1;
+        }
+    }
+    Ok((attributes, line, pos))
+}
+
+//  `[code ...]` keeps its content exactly as written, so only `#id`, `.class` and a leading `lang=` are taken.
+//  Braces and other `key=value` words are part of the code.
+pub fn parse_code_attributes(
+    p: &mut Parser,
+    line: Line,
+    mut pos: Pos,
+) -> Result<(Attributes, Line, Pos)> {
+    let mut attributes = Attributes::new();
+    pos = parse_shorthand(p, line, pos, &mut attributes)?;
+    if p.lines[line]
+        .get(pos..pos + 5)
+        .is_some_and(|key| key.iter().copied().eq("lang=".chars()))
+    {
+        pos = parse_pair(p, line, pos, &mut attributes)?;
+        if p.lines[line].get(pos).is_some_and(|&c| is_whitespace(c)) {
+            pos += 1;
+        }
+    }
+    Ok((attributes, line, pos))
+}
+
+//  `#id` and `.class` written onto the name, along with the whitespace after them.
+fn parse_shorthand(
+    p: &mut Parser,
+    line: Line,
+    mut pos: Pos,
+    attributes: &mut Attributes,
+) -> Result<Pos> {
+    let mut shorthand = false;
+    while let Some(&c @ ('#' | '.')) = p.lines[line].get(pos) {
+        shorthand = true;
+        let at = Position::new(line, pos);
+        pos += 1;
+        let value: String = p.lines[line][pos..]
+            .iter()
+            .take_while(|&&c| !is_whitespace(c) && !['#', '.', ']', '{'].contains(&c))
+            .collect();
+        pos += value.chars().count();
+        if value.is_empty() {
+            p.recover(Error::at(at, ErrorType::BadAttribute))?;
+            continue;
+        }
+        set_attribute(attributes, if c == '#' { "id" } else { "class" }, value);
+    }
+    if shorthand && p.lines[line].get(pos).is_some_and(|&c| is_whitespace(c)) {
+        pos += 1;
+    }
+    Ok(pos)
+}
+
+//  Expects the opening '{', the pairs inside may go over multiple lines.
+fn parse_braces(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+    attributes: &mut Attributes,
+) -> Result<(Line, Pos)> {
+    let open = Position::new(line, pos);
+    pos += 1;
+    loop {
+        let Some(vline) = p.lines.get(line) else {
+            p.recover(Error::at(open, ErrorType::BadAttribute))?;
+            return Ok((line, 0));
+        };
+        match vline.get(pos) {
+            None => {
+                line += 1;
+                pos = 0;
+            }
+            Some(&c) if is_whitespace(c) => pos += 1,
+            Some('}') => return Ok((line, pos + 1)),
+            Some(_) if vline.get(key_len(&vline[pos..]) + pos) == Some(&'=') => {
+                pos = parse_pair(p, line, pos, attributes)?;
+            }
+            Some(_) => {
+                let start = Position::new(line, pos);
+                while vline
+                    .get(pos)
+                    .is_some_and(|&c| !is_whitespace(c) && c != '}')
+                {
+                    pos += 1;
+                }
+                p.recover(Error::span(
+                    Span::new(start, Position::new(line, pos)),
+                    ErrorType::BadAttribute,
+                ))?;
+            }
+        }
+    }
+}
+
+//  Expects a key followed by '='.
+fn parse_pair(p: &mut Parser, line: Line, pos: Pos, attributes: &mut Attributes) -> Result<Pos> {
+    let len = key_len(&p.lines[line][pos..]);
+    let key: String = p.lines[line][pos..pos + len].iter().collect();
+    let (value, end) = parse_value(p, line, pos + len + 1, ErrorType::BadAttribute)?;
+    //  Event handlers and script addresses would let scripts into the page through otherwise harmless looking markup.
+    if key.to_ascii_lowercase().starts_with("on") || is_unsafe_url(&value, true) {
+        p.recover(Error::span(
+            Span::new(Position::new(line, pos), Position::new(line, pos + len)),
+            ErrorType::BadAttribute,
+        ))?;
+    } else {
+        set_attribute(attributes, &key, value);
+    }
+    Ok(end)
+}
+
+//  Either quoted, where `\"` and `\\` can be escaped, or running until the next whitespace, `]` or `}`.
+//  Unclosed quotes are reported as `error`.
+pub(crate) fn parse_value(
+    p: &mut Parser,
+    line: Line,
+    mut pos: Pos,
+    error: ErrorType,
+) -> Result<(String, Pos)> {
+    let vline = &p.lines[line];
+    let value_start = Position::new(line, pos);
+    let mut value = String::new();
+    if vline.get(pos) != Some(&'"') {
+        while let Some(&c) = vline
+            .get(pos)
+            .filter(|&&c| !is_whitespace(c) && c != ']' && c != '}')
+        {
+            value.push(c);
+            pos += 1;
+        }
+        return Ok((value, pos));
+    }
+    pos += 1;
+    let mut escaped = false;
+    loop {
+        match vline.get(pos) {
+            Some(&c) if escaped => {
+                value.push(c);
+                escaped = false;
+            }
+            Some('\\') => escaped = true,
+            Some('"') => break,
+            Some(&c) => value.push(c),
+            None => {
+                p.recover(Error::span(
+                    Span::new(value_start, Position::new(line, pos)),
+                    error,
+                ))?;
+                return Ok((value, pos));
+            }
+        }
+        pos += 1;
+    }
+    Ok((value, pos + 1))
+}
+
+//  Keys start with a letter and go on with letters, digits, `-` and `_`.
+pub(crate) fn key_len(rest: &[char]) -> usize {
+    if !rest.first().is_some_and(|c| c.is_ascii_alphabetic()) {
+        return 0;
+    }
+    rest.iter()
+        .take_while(|&&c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        .count()
+}
+
+//  Classes add up, anything else is overwritten.
+pub fn set_attribute(attributes: &mut Attributes, key: &str, value: String) {
+    match attributes.iter_mut().find(|(k, _)| k == key) {
+        Some((_, v)) if key == "class" => {
+            v.push(' ');
+            v.push_str(&value);
+        }
+        Some((_, v)) => *v = value,
+        None => attributes.push((key.to_string(), value)),
+    }
+}
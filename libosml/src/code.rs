@@ -0,0 +1,78 @@
+use super::{Attributes, Code, Error, ErrorType, Line, Node, Parser, Pos, Position, Result, Span};
+
+//  `[code ...]` or `[code lang=rust ...]`, or for code with unbalanced brackets, `[code ``` ... ```]` with any number of backticks.
+//  Everything inside is kept exactly as written, no formatting, escapes or collapsed whitespace.
+//  Without a fence the code ends at the `]` matching the block's `[`.
+//  A blank rest of the opening line and a blank closing line are left out, so the code can sit on lines of its own.
+//  Expects the character after the name.
+pub fn parse_code(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+    start: Position,
+    mut attributes: Attributes,
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let mut lang = None;
+    attributes.retain(|(key, value)| match key.as_str() {
+        "lang" => {
+            lang = Some(value.clone()).filter(|lang| !lang.is_empty());
+            false
+        }
+        _ => true,
+    });
+
+    let fence = p.lines[line]
+        .get(pos..)
+        .map_or(0, |rest| rest.iter().take_while(|&&c| c == '`').count());
+    pos += fence;
+
+    let mut text = String::new();
+    let mut depth = 0;
+    loop {
+        let Some(vline) = p.lines.get(line) else {
+            p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+            return Ok((line, 0));
+        };
+        let Some(&c) = vline.get(pos) else {
+            text.push('\n');
+            line += 1;
+            pos = 0;
+            continue;
+        };
+        if fence == 0 {
+            match c {
+                '[' => depth += 1,
+                ']' if depth == 0 => break,
+                ']' => depth -= 1,
+                _ => {}
+            }
+        } else if vline[pos..].iter().take_while(|&&c| c == '`').count() == fence
+            && vline.get(pos + fence) == Some(&']')
+        {
+            pos += fence;
+            break;
+        }
+        text.push(c);
+        pos += 1;
+    }
+
+    output.push(Node::Code(Code {
+        attributes,
+        lang,
+        text: trim_blank_lines(&text).to_string(),
+        span: Span::new(start, Position::new(line, pos + 1)),
+    }));
+    Ok((line, pos + 1))
+}
+
+pub(crate) fn trim_blank_lines(text: &str) -> &str {
+    let text = match text.split_once('\n') {
+        Some((first, rest)) if first.trim().is_empty() => rest,
+        _ => text,
+    };
+    match text.rsplit_once('\n') {
+        Some((rest, last)) if last.trim().is_empty() => rest,
+        _ => text,
+    }
+}
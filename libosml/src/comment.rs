@@ -0,0 +1,34 @@
+use super::{Error, ErrorType, Line, Parser, Pos, Position, Result};
+
+//  `%% ...` runs until the end of the line and `%{ ... }%` until its closing `}%`, possibly lines later.
+//  Returns where the comment starting at `line`/`pos` ends, or `None` if there isn't one.
+//  An unclosed `%{` takes the rest of the document with it.
+pub fn skip_comment(p: &mut Parser, line: Line, pos: Pos) -> Result<Option<(Line, Pos)>> {
+    let vline = &p.lines[line];
+    if vline.get(pos) != Some(&'%') {
+        return Ok(None);
+    }
+    match vline.get(pos + 1) {
+        Some('%') => Ok(Some((line, vline.len()))),
+        Some('{') => {
+            let (mut end_line, mut end_pos) = (line, pos + 2);
+            while let Some(vline) = p.lines.get(end_line) {
+                match vline.get(end_pos..).unwrap_or_default() {
+                    ['}', '%', ..] => return Ok(Some((end_line, end_pos + 2))),
+                    [] => {
+                        end_line += 1;
+                        end_pos = 0;
+                    }
+                    _ => end_pos += 1,
+                }
+            }
+            p.recover(Error::at(
+                Position::new(line, pos),
+                ErrorType::UnclosedComment,
+            ))?;
+            let last = p.lines.len() - 1;
+            Ok(Some((last, p.lines[last].len())))
+        }
+        _ => Ok(None),
+    }
+}
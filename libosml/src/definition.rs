@@ -0,0 +1,80 @@
+use super::{
+    is_whitespace, item_end, parse_continuation, parse_text_line, trim_inlines, Definition,
+    DefinitionList, InlineBuilder, Line, Node, Parser, Pos, Position, Result, Span, TextMode,
+};
+
+//  Whether the `:` at `pos` starts a term, which it only does with a definition indented past it on the next line.
+//  Otherwise the line is text, as in `[a :) hi]`.
+pub(crate) fn starts_definition(p: &Parser, line: Line, pos: Pos) -> bool {
+    let vline = &p.lines[line];
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, &c) in vline.iter().enumerate().skip(pos + 1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '%' if matches!(vline.get(i + 1), Some('%' | '{')) => break,
+            '[' => depth += 1,
+            //  The block ends on the same line, so nothing can follow.
+            ']' if depth == 0 => return false,
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    p.lines.get(line + 1).is_some_and(|next| {
+        let indent = next.iter().take_while(|&&c| is_whitespace(c)).count();
+        indent > pos && indent < next.len() && !matches!(next[indent], '+' | '=' | ':')
+    })
+}
+
+//  `: term` with the definition on the lines indented past the `:` below it.
+//  Terms right after each other make up one list. Expects the `:`.
+pub fn parse_definition(
+    p: &mut Parser,
+    line: Line,
+    pos: Pos,
+    output: &mut Vec<Node>,
+    block_start: Position,
+) -> Result<(bool, Line, Pos)> {
+    let start = Position::new(line, pos);
+    let mut term = Vec::new();
+    let mut inline = InlineBuilder::default();
+    let (done, nline, npos) = parse_text_line(
+        p,
+        line,
+        pos + 1,
+        &mut term,
+        &mut inline,
+        TextMode::ListItem,
+        block_start,
+    )?;
+    //  The term is a line of its own.
+    if !done {
+        inline.finish(p, item_end(p, done, nline, npos), &mut term)?;
+    }
+    let mut children = Vec::new();
+    let (done, nline, npos) = match done {
+        true => (done, nline, npos),
+        false => parse_continuation(p, nline, npos, pos, &mut children, &mut inline, block_start)?,
+    };
+    trim_inlines(&mut term);
+    trim_inlines(&mut children);
+    let span = Span::new(start, item_end(p, done, nline, npos));
+    let definition = Definition {
+        term,
+        children,
+        span,
+    };
+
+    match output.last_mut() {
+        Some(Node::DefinitionList(list)) => {
+            list.span.end = span.end;
+            list.entries.push(definition);
+        }
+        _ => output.push(Node::DefinitionList(DefinitionList {
+            entries: vec![definition],
+            span,
+        })),
+    }
+    Ok((done, nline, npos))
+}
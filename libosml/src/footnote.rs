@@ -0,0 +1,152 @@
+use super::{
+    group_paragraphs, is_whitespace, parse_block_body, trim_inlines, Document, Error, ErrorType,
+    Footnote, FootnoteRef, Line, Node, Parser, Pos, Position, Result, Span,
+};
+use std::path::PathBuf;
+
+//  `[^id]`, see `resolve_footnotes`. Expects the `^`.
+pub fn parse_footnote_ref(
+    p: &mut Parser,
+    line: Line,
+    pos: Pos,
+    start: Position,
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let vline = &p.lines[line];
+    let mut end = pos + 1;
+    while vline.get(end).is_some_and(|&c| c != ']') {
+        end += 1;
+    }
+    let id: String = vline[pos + 1..end].iter().collect();
+    let span = Span::new(start, Position::new(line, end + 1));
+    let next = if end < vline.len() {
+        (line, end + 1)
+    } else {
+        p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+        (line + 1, 0)
+    };
+    if id.is_empty() || id.chars().any(is_whitespace) {
+        p.recover(Error::span(span, ErrorType::BadFootnoteRef))?;
+        return Ok(next);
+    }
+
+    p.footnote_refs.push((id.clone(), span, None));
+    output.push(Node::FootnoteRef(FootnoteRef {
+        id,
+        number: None,
+        index: 0,
+        span,
+    }));
+    Ok(next)
+}
+
+//  `[footnote id Some *text*]`, which can go anywhere in the document, before or after its references.
+//  Expects the character after the name.
+pub fn parse_footnote(
+    p: &mut Parser,
+    line: Line,
+    mut pos: Pos,
+    start: Position,
+) -> Result<(Line, Pos)> {
+    let vline = &p.lines[line];
+    let id: String = vline[pos.min(vline.len())..]
+        .iter()
+        .take_while(|&&c| !is_whitespace(c) && c != ']')
+        .collect();
+    pos += id.chars().count();
+    if id.is_empty() {
+        p.recover(Error::at(start, ErrorType::NoFootnoteId))?;
+    }
+
+    let (line, pos, children) = parse_block_body(p, line, pos, start)?;
+    let mut children = match p.ctx.legacy_breaks {
+        true => children,
+        false => group_paragraphs(children),
+    };
+    trim_inlines(&mut children);
+    if !id.is_empty() {
+        let footnote = Footnote {
+            id,
+            number: 0,
+            references: 0,
+            children,
+            span: Span::new(start, Position::new(line, pos)),
+        };
+        p.footnotes.push((footnote, None));
+    }
+    Ok((line, pos))
+}
+
+//  Runs once everything is parsed, includes too, as notes can come after their references.
+//  Notes are numbered by their first reference, notes without any are left out with a warning.
+pub(crate) fn resolve_footnotes(p: &mut Parser, document: &mut Document) -> Result<()> {
+    let in_file = |span: Span, error: ErrorType, file: Option<PathBuf>| Error {
+        file,
+        ..Error::span(span, error)
+    };
+
+    let mut notes: Vec<(Footnote, Option<PathBuf>)> = Vec::new();
+    for (note, file) in std::mem::take(&mut p.footnotes) {
+        if notes.iter().any(|(other, _)| other.id == note.id) {
+            p.recover(in_file(note.span, ErrorType::DuplicateFootnote, file))?;
+            continue;
+        }
+        notes.push((note, file));
+    }
+    let ids: Vec<String> = notes.iter().map(|(note, _)| note.id.clone()).collect();
+    for (id, span, file) in std::mem::take(&mut p.footnote_refs) {
+        if !ids.contains(&id) {
+            p.recover(in_file(span, ErrorType::UndefinedFootnote, file))?;
+        }
+    }
+
+    //  Notes can reference other notes, which are numbered after everything in the document.
+    let mut order = Vec::new();
+    number_refs(&mut document.children, &ids, &mut order);
+    let mut i = 0;
+    while i < order.len() {
+        let (note, _) = notes
+            .iter_mut()
+            .find(|(note, _)| note.id == order[i].0)
+            .unwrap();
+        number_refs(&mut note.children, &ids, &mut order);
+        i += 1;
+    }
+
+    for (mut note, file) in notes {
+        match order.iter().position(|(id, _)| *id == note.id) {
+            Some(i) => {
+                note.number = i + 1;
+                note.references = order[i].1;
+                document.footnotes.push(note);
+            }
+            None => p.warn(in_file(note.span, ErrorType::UnusedFootnote, file)),
+        }
+    }
+    document.footnotes.sort_by_key(|note| note.number);
+    Ok(())
+}
+
+//  `order` has the ids in order of their first reference, along with how many references each has.
+fn number_refs(nodes: &mut [Node], ids: &[String], order: &mut Vec<(String, usize)>) {
+    for node in nodes {
+        match node {
+            Node::FootnoteRef(footnote) if ids.contains(&footnote.id) => {
+                let i = match order.iter().position(|(id, _)| *id == footnote.id) {
+                    Some(i) => i,
+                    None => {
+                        order.push((footnote.id.clone(), 0));
+                        order.len() - 1
+                    }
+                };
+                order[i].1 += 1;
+                footnote.number = Some(i + 1);
+                footnote.index = order[i].1;
+            }
+            _ => node
+                .children_mut()
+                .into_iter()
+                .for_each(|children| number_refs(children, ids, order)),
+        }
+    }
+}
@@ -0,0 +1,428 @@
+//  A small offline highlighter for `[code lang=... ...]`, good enough for docs rather than a full lexer.
+//  Every token kind has a stable CSS class, so themes can rely on `hl-keyword` and friends.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    Literal,
+    Number,
+    String,
+    Escape,
+    Comment,
+    Variable,
+    Tag,
+    Attribute,
+}
+
+impl TokenKind {
+    pub const fn class(self) -> &'static str {
+        match self {
+            Self::Keyword => "hl-keyword",
+            Self::Type => "hl-type",
+            Self::Function => "hl-function",
+            Self::Macro => "hl-macro",
+            Self::Literal => "hl-literal",
+            Self::Number => "hl-number",
+            Self::String => "hl-string",
+            Self::Escape => "hl-escape",
+            Self::Comment => "hl-comment",
+            Self::Variable => "hl-variable",
+            Self::Tag => "hl-tag",
+            Self::Attribute => "hl-attribute",
+        }
+    }
+}
+
+//  Plain text has no kind, joining the text of every token gives back the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: Option<TokenKind>,
+    pub text: String,
+}
+
+//  `None` when the language isn't one of rust, shell, json, toml, ron, html or osml.
+pub fn highlight(lang: &str, code: &str) -> Option<Vec<Token>> {
+    let mut lexer = Lexer {
+        chars: code.chars().collect(),
+        pos: 0,
+        tokens: Vec::new(),
+    };
+    match lang {
+        "rust" | "rs" => lexer.generic(&RUST),
+        "shell" | "sh" | "bash" => lexer.generic(&SHELL),
+        "json" => lexer.generic(&JSON),
+        "toml" => lexer.generic(&TOML),
+        "ron" => lexer.generic(&RON),
+        "html" => lexer.html(),
+        "osml" => lexer.osml(),
+        _ => return None,
+    }
+    Some(lexer.tokens)
+}
+
+//  Everything the generic lexer needs to know about a language.
+struct Language {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    keywords: &'static [&'static str],
+    literals: &'static [&'static str],
+    types: &'static [&'static str],
+    //  Capitalized names are types.
+    capitalized_types: bool,
+    //  Names and strings followed by this are keys.
+    key_separator: Option<char>,
+    //  `name!` is a macro and `name(` a function.
+    calls: bool,
+    //  `'c'`, as opposed to a single quoted string.
+    chars: bool,
+    //  `$name` is a variable and `#` only starts a comment at the start of a word.
+    variables: bool,
+    //  `[table]` at the start of a line.
+    headers: bool,
+}
+
+const NONE: Language = Language {
+    line_comments: &[],
+    block_comment: None,
+    quotes: &[],
+    keywords: &[],
+    literals: &[],
+    types: &[],
+    capitalized_types: false,
+    key_separator: None,
+    calls: false,
+    chars: false,
+    variables: false,
+    headers: false,
+};
+
+const RUST: Language = Language {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "unsafe", "use", "where", "while",
+    ],
+    literals: &["true", "false"],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    capitalized_types: true,
+    calls: true,
+    chars: true,
+    ..NONE
+};
+
+const SHELL: Language = Language {
+    line_comments: &["#"],
+    quotes: &['"', '\''],
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "in", "function", "return", "exit", "export", "local", "readonly",
+    ],
+    variables: true,
+    ..NONE
+};
+
+const JSON: Language = Language {
+    quotes: &['"'],
+    literals: &["true", "false", "null"],
+    key_separator: Some(':'),
+    ..NONE
+};
+
+const TOML: Language = Language {
+    line_comments: &["#"],
+    quotes: &['"', '\''],
+    literals: &["true", "false"],
+    key_separator: Some('='),
+    headers: true,
+    ..NONE
+};
+
+const RON: Language = Language {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    literals: &["true", "false"],
+    capitalized_types: true,
+    key_separator: Some(':'),
+    chars: true,
+    ..NONE
+};
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    tokens: Vec<Token>,
+}
+
+impl Lexer {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.peek(i) == Some(c))
+    }
+
+    //  Whether the next character, ignoring spaces, is `c`.
+    fn followed_by(&self, c: char) -> bool {
+        self.chars[self.pos..]
+            .iter()
+            .find(|&&c| c != ' ' && c != '\t')
+            == Some(&c)
+    }
+
+    fn at_word_start(&self) -> bool {
+        self.pos == 0 || !is_word(self.chars[self.pos - 1])
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.chars[..self.pos]
+            .iter()
+            .rev()
+            .take_while(|&&c| c != '\n')
+            .all(|&c| c == ' ' || c == '\t')
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.peek(0).is_some_and(&f) {
+            self.pos += 1;
+        }
+    }
+
+    //  Up to and including `end`, or the rest of the code if there is none.
+    fn take_through(&mut self, end: &str) {
+        while self.pos < self.chars.len() && !self.starts_with(end) {
+            self.pos += 1;
+        }
+        self.pos = (self.pos + end.chars().count()).min(self.chars.len());
+    }
+
+    //  Expects the opening quote.
+    fn take_string(&mut self, quote: char, escapes: bool) {
+        self.pos += 1;
+        while let Some(c) = self.peek(0) {
+            self.pos += 1;
+            if escapes && c == '\\' {
+                self.pos = (self.pos + 1).min(self.chars.len());
+            } else if c == quote {
+                break;
+            }
+        }
+    }
+
+    //  Everything from `start` up to the current position becomes one token.
+    fn emit(&mut self, kind: Option<TokenKind>, start: usize) {
+        if start == self.pos {
+            return;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match self.tokens.last_mut() {
+            Some(last) if kind.is_none() && last.kind.is_none() => last.text.push_str(&text),
+            _ => self.tokens.push(Token { kind, text }),
+        }
+    }
+
+    fn generic(&mut self, lang: &Language) {
+        while let Some(c) = self.peek(0) {
+            let start = self.pos;
+            let kind = if lang.line_comments.iter().any(|s| self.starts_with(s))
+                && (!lang.variables || start == 0 || self.chars[start - 1].is_whitespace())
+            {
+                self.take_while(|c| c != '\n');
+                Some(TokenKind::Comment)
+            } else if let Some((open, close)) = lang
+                .block_comment
+                .filter(|(open, _)| self.starts_with(open))
+            {
+                self.pos += open.chars().count();
+                self.take_through(close);
+                Some(TokenKind::Comment)
+            } else if lang.quotes.contains(&c) {
+                self.take_string(c, c != '\'');
+                match lang.key_separator {
+                    Some(separator) if self.followed_by(separator) => Some(TokenKind::Attribute),
+                    _ => Some(TokenKind::String),
+                }
+            } else if lang.chars && c == '\'' {
+                if self.peek(1) == Some('\\') || self.peek(2) == Some('\'') {
+                    self.take_string('\'', true);
+                    Some(TokenKind::String)
+                } else {
+                    //  A lifetime or label.
+                    self.pos += 1;
+                    self.take_while(is_word);
+                    None
+                }
+            } else if lang.variables && c == '$' {
+                self.pos += 1;
+                if self.peek(0) == Some('{') {
+                    self.take_through("}");
+                } else if self.peek(0).is_some_and(is_word) {
+                    self.take_while(is_word);
+                } else {
+                    self.pos = (self.pos + 1).min(self.chars.len());
+                }
+                Some(TokenKind::Variable)
+            } else if lang.headers && c == '[' && self.at_line_start() {
+                self.take_through("]");
+                Some(TokenKind::Tag)
+            } else if c.is_ascii_digit() && self.at_word_start() {
+                self.take_while(is_word);
+                while self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit())
+                {
+                    self.pos += 1;
+                    self.take_while(is_word);
+                }
+                Some(TokenKind::Number)
+            } else if is_word(c) {
+                self.take_while(is_word);
+                let word: String = self.chars[start..self.pos].iter().collect();
+                let word = word.as_str();
+                if lang.keywords.contains(&word) {
+                    Some(TokenKind::Keyword)
+                } else if lang.literals.contains(&word) {
+                    Some(TokenKind::Literal)
+                } else if lang.calls && self.peek(0) == Some('!') && self.peek(1) != Some('=') {
+                    self.pos += 1;
+                    Some(TokenKind::Macro)
+                } else if lang
+                    .key_separator
+                    .is_some_and(|separator| self.followed_by(separator))
+                {
+                    Some(TokenKind::Attribute)
+                } else if lang.types.contains(&word)
+                    || (lang.capitalized_types && word.starts_with(|c: char| c.is_uppercase()))
+                {
+                    Some(TokenKind::Type)
+                } else if lang.calls && self.peek(0) == Some('(') {
+                    Some(TokenKind::Function)
+                } else {
+                    None
+                }
+            } else {
+                self.pos += 1;
+                None
+            };
+            self.emit(kind, start);
+        }
+    }
+
+    fn html(&mut self) {
+        while let Some(c) = self.peek(0) {
+            let start = self.pos;
+            if self.starts_with("<!--") {
+                self.take_through("-->");
+                self.emit(Some(TokenKind::Comment), start);
+            } else if c == '<'
+                && self
+                    .peek(1)
+                    .is_some_and(|c| c.is_alphabetic() || c == '/' || c == '!')
+            {
+                self.pos += 1;
+                if matches!(self.peek(0), Some('/' | '!')) {
+                    self.pos += 1;
+                }
+                self.emit(None, start);
+                let start = self.pos;
+                self.take_while(|c| is_word(c) || c == '-' || c == ':');
+                self.emit(Some(TokenKind::Tag), start);
+                self.html_attributes();
+            } else if c == '&' {
+                self.pos += 1;
+                self.take_while(|c| is_word(c) || c == '#');
+                if self.peek(0) == Some(';') {
+                    self.pos += 1;
+                }
+                self.emit(Some(TokenKind::Escape), start);
+            } else {
+                self.pos += 1;
+                self.emit(None, start);
+            }
+        }
+    }
+
+    //  Everything up to and including the `>` closing a tag.
+    fn html_attributes(&mut self) {
+        while let Some(c) = self.peek(0) {
+            let start = self.pos;
+            let kind = match c {
+                '"' | '\'' => {
+                    self.take_string(c, false);
+                    Some(TokenKind::String)
+                }
+                _ if is_word(c) => {
+                    self.take_while(|c| is_word(c) || c == '-' || c == ':');
+                    Some(TokenKind::Attribute)
+                }
+                _ => {
+                    self.pos += 1;
+                    None
+                }
+            };
+            self.emit(kind, start);
+            if c == '>' {
+                break;
+            }
+        }
+    }
+
+    fn osml(&mut self) {
+        while let Some(c) = self.peek(0) {
+            let start = self.pos;
+            let kind = match c {
+                '\\' => {
+                    self.pos = (self.pos + 2).min(self.chars.len());
+                    Some(TokenKind::Escape)
+                }
+                '%' if self.peek(1) == Some('%') => {
+                    self.take_while(|c| c != '\n');
+                    Some(TokenKind::Comment)
+                }
+                '%' if self.peek(1) == Some('{') => {
+                    self.take_through("}%");
+                    Some(TokenKind::Comment)
+                }
+                '[' => {
+                    self.pos += 1;
+                    self.emit(None, start);
+                    let start = self.pos;
+                    self.take_while(is_word);
+                    self.emit(Some(TokenKind::Tag), start);
+                    continue;
+                }
+                '+' | '=' if self.at_line_start() => {
+                    self.take_while(|next| next == c);
+                    Some(TokenKind::Keyword)
+                }
+                '{' if self.peek(1) == Some('{') => {
+                    self.take_through("}}");
+                    Some(TokenKind::Variable)
+                }
+                '*' | '/' | '_' | '~' => {
+                    self.pos += 1;
+                    Some(TokenKind::Keyword)
+                }
+                _ => {
+                    self.pos += 1;
+                    None
+                }
+            };
+            self.emit(kind, start);
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
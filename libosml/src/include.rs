@@ -0,0 +1,96 @@
+use super::{parse_blocks, Error, ErrorType, Line, Node, Parser, Pos, Position, Result, Span};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//  `[include partials/nav.osml]`
+//  Splices the blocks of another file in place of the include, the path being relative to the including file.
+//  Metadata of included files is ignored, it belongs to the page doing the including.
+//  Expects the character after the name.
+pub fn parse_include(
+    p: &mut Parser,
+    line: Line,
+    mut pos: Pos,
+    start: Position,
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let vline = &p.lines[line];
+    let path_start = pos;
+    while vline.get(pos).is_some_and(|&c| c != ']') {
+        pos += 1;
+    }
+    let raw: String = vline[path_start..pos].iter().collect();
+    let span = Span::new(Position::new(line, path_start), Position::new(line, pos));
+    //  The path can't go over multiple lines, so the rest of the line is taken as the path.
+    let end = if pos < vline.len() {
+        (line, pos + 1)
+    } else {
+        p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+        (line + 1, 0)
+    };
+
+    let Some(including) = &p.path else {
+        p.recover(Error::span(span, ErrorType::IncludeWithoutPath))?;
+        return Ok(end);
+    };
+    let path = including.parent().unwrap_or(Path::new("")).join(raw.trim());
+    let Ok(source) = fs::read_to_string(&path) else {
+        p.recover(Error::span(span, ErrorType::IncludeNotFound))?;
+        return Ok(end);
+    };
+    let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+    if p.include_stack.contains(&canonical) {
+        p.recover(Error::span(span, ErrorType::IncludeCycle))?;
+        return Ok(end);
+    }
+    p.includes.push(path.clone());
+
+    let mut include_stack = p.include_stack.clone();
+    include_stack.push(canonical);
+    let mut included = Parser::new(&source, p.ctx, p.recovering);
+    included.path = Some(path.clone());
+    included.include_stack = include_stack;
+    included.variables = std::mem::take(&mut p.variables);
+    included.macros = std::mem::take(&mut p.macros);
+    included.macro_text = p.macro_text;
+    //  Macros expanding through an include are still as deep as the include is.
+    included.macro_depth = p.macro_depth;
+    let res = parse_blocks(&mut included, output);
+    let Parser {
+        errors,
+        warnings,
+        includes,
+        footnotes,
+        footnote_refs,
+        variables,
+        macros,
+        macro_text,
+        ..
+    } = included;
+    p.variables = variables;
+    p.macros = macros;
+    p.macro_text = macro_text;
+
+    //  Errors from nested includes already know their file.
+    let in_file = |mut e: Error| {
+        e.file.get_or_insert_with(|| path.clone());
+        e
+    };
+    p.errors.extend(errors.into_iter().map(in_file));
+    p.warnings.extend(warnings.into_iter().map(in_file));
+    p.includes.extend(includes);
+    let file = |file: Option<PathBuf>| file.or_else(|| Some(path.clone()));
+    p.footnotes.extend(
+        footnotes
+            .into_iter()
+            .map(|(footnote, f)| (footnote, file(f))),
+    );
+    p.footnote_refs.extend(
+        footnote_refs
+            .into_iter()
+            .map(|(id, span, f)| (id, span, file(f))),
+    );
+    if let Err(e) = res {
+        p.recover(in_file(e))?;
+    }
+    Ok(end)
+}
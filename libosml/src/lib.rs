@@ -1,26 +1,94 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
+mod ast;
+mod attributes;
+mod code;
+mod comment;
+mod definition;
+mod footnote;
+mod highlight;
+mod include;
+mod link;
+mod macros;
+mod meta;
+mod paragraph;
+mod plugin;
+mod render;
+mod table;
+mod template;
 #[cfg(test)]
 mod test;
+mod toc;
+mod variable;
+
+pub use ast::*;
+pub use attributes::*;
+pub use code::*;
+pub use comment::*;
+pub use definition::*;
+pub use footnote::*;
+pub use highlight::*;
+pub use include::*;
+pub use link::*;
+pub use macros::*;
+pub use meta::*;
+pub use paragraph::*;
+pub use plugin::*;
+pub use render::*;
+pub use table::*;
+pub use template::*;
+pub use toc::*;
+pub use variable::*;
 
 pub type Pos = usize;
 pub type Line = usize;
-pub type ExtCallback = fn(
-    lines: &Vec<Vec<char>>,
-    line: Line,
-    pos: Pos,
-    output: String,
-    ctx: &Context,
-) -> Result<(Line, Pos, String)>;
-
 pub struct Context {
-    pub plugins: HashMap<String, ExtCallback>,
+    pub plugins: HashMap<String, Box<dyn Plugin>>,
+    //  Falls back to a bare `<html><head></head><body>` wrapper.
+    pub template: Option<Template>,
+    //  The file being parsed, `[include ...]` paths are relative to it.
+    //  Includes are refused without one, so documents from strings can't read files.
+    pub path: Option<PathBuf>,
+    //  Block names that become a proper element rather than a `<div>`, the class is kept either way.
+    pub elements: HashMap<String, String>,
+    //  Keeps the old `<br><br>` between paragraphs rather than grouping them into `<p>`.
+    pub legacy_breaks: bool,
+    //  Characters that toggle formatting in text, escaping one writes it as is.
+    pub delimiters: HashMap<char, Format>,
+    //  Values for `{{name}}` in text, which `[let name value]` adds to for the rest of the document.
+    pub variables: HashMap<String, String>,
 }
 
 impl Context {
     pub fn create() -> Self {
         Context {
             plugins: HashMap::new(),
+            template: None,
+            path: None,
+            elements: [
+                ("title", "h1"),
+                ("subtitle", "h2"),
+                ("section", "section"),
+                ("quote", "blockquote"),
+                ("aside", "aside"),
+                ("nav", "nav"),
+                ("footer", "footer"),
+                ("toc", "nav"),
+            ]
+            .into_iter()
+            .map(|(name, element)| (name.to_string(), element.to_string()))
+            .collect(),
+            legacy_breaks: false,
+            delimiters: [
+                ('*', Format::Bold),
+                ('/', Format::Italic),
+                ('_', Format::Underline),
+                ('~', Format::Strikethrough),
+            ]
+            .into_iter()
+            .collect(),
+            variables: HashMap::new(),
         }
     }
 }
@@ -29,12 +97,17 @@ impl Context {
 pub enum Location {
     Null,
     Absolute(Line),
+    Span(Span),
 }
 
 #[derive(Debug, Clone)]
 pub struct Error {
     pub location: Location,
     pub error: ErrorType,
+    //  Set when the error is in an included file rather than the document itself.
+    pub file: Option<PathBuf>,
+    //  Another place worth pointing at, like where the macro an error came out of is defined.
+    pub note: Option<Box<Error>>,
 }
 
 impl Error {
@@ -42,6 +115,8 @@ impl Error {
         Error {
             location: Location::Null,
             error: et,
+            file: None,
+            note: None,
         }
     }
 
@@ -49,13 +124,27 @@ impl Error {
         Error {
             location: Location::Absolute(line),
             error: et,
+            file: None,
+            note: None,
         }
     }
+
+    pub fn span(span: Span, et: ErrorType) -> Self {
+        Error {
+            location: Location::Span(span),
+            error: et,
+            file: None,
+            note: None,
+        }
+    }
+
+    pub fn at(at: Position, et: ErrorType) -> Self {
+        Self::span(Span::at(at), et)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum ErrorType {
-    UnexpectedEnd(String),
     BlockNameNoEnd, //  Impossible Error?
     ExpectedBlockStart,
     BlockNoEnd,
@@ -67,33 +156,50 @@ pub enum ErrorType {
     StrayBackslash,
     RecursiveList,
     InvalidListDepth,
+    NoTemplateContent,
+    BadMetadata,
+    BadMetadataDate,
+    IncludeWithoutPath,
+    IncludeNotFound,
+    IncludeCycle,
+    NoLinkTarget,
+    UnsafeLinkTarget,
+    MissingAltText,
+    BadTableRow,
+    BadAttribute,
+    UnclosedComment,
+    BadFootnoteRef,
+    NoFootnoteId,
+    UndefinedFootnote,
+    DuplicateFootnote,
+    UnusedFootnote,
+    BadVariable,
+    UndefinedVariable,
+    BadMacro,
+    MacroArguments,
+    MacroRecursion,
+    MacroTooLarge,
+    MacroDefinedHere,
     OtherError(String),
 }
 
 impl ErrorType {
+    //  With the default delimiters, see `message_in`.
     pub fn message(&self) -> String {
+        self.message_in(&Context::create())
+    }
+
+    //  Unclosed formatting is described with the delimiters `ctx` uses for it.
+    pub fn message_in(&self, ctx: &Context) -> String {
         match self {
-            Self::UnexpectedEnd(_) => "This error is used internally.",
             Self::ExpectedBlockStart => "Text cannot be placed outside of block: `[ ... ]`.",
             Self::BlockNameNoEnd => "Block's name is not defined correctly as `[my_name ...]`.",
             Self::BlockNoEnd => "Block's opening `[` is not matched with a corresponding `]`.",
             Self::BadBlockName => "Block names must only use characters 0-9, a-z, A-Z, or '_'.",
-            Self::UnclosedBold => {
-                "Opening `*` must be matched with a closing `*`. \
-                                         Or, you meant to escape the `*` with `\\*`."
-            }
-            Self::UnclosedItalic => {
-                "Opening `/` must be matched with a closing `/`. \
-                Or, you meant to escape the `/` with `\\/`."
-            }
-            Self::UnclosedUnderline => {
-                "Opening `_` must be matched with a closing `_`. \
-                Or, you meant to escape the `_` with `\\_`."
-            }
-            Self::UnclosedStrikethrough => {
-                "Opening `~` must be matched with a closing `~`. \
-                Or, you meant to escape the `~` with `\\~`."
-            }
+            Self::UnclosedBold => return unclosed_message(ctx, Format::Bold),
+            Self::UnclosedItalic => return unclosed_message(ctx, Format::Italic),
+            Self::UnclosedUnderline => return unclosed_message(ctx, Format::Underline),
+            Self::UnclosedStrikethrough => return unclosed_message(ctx, Format::Strikethrough),
             Self::StrayBackslash => {
                 "A stray `\\` is not allowed. \
                 However, you can escape it using `\\\\`."
@@ -108,34 +214,252 @@ impl ErrorType {
                 "List nesting depth is invalid. In other words: \
                 `+ Layer One` cannot be followed by `++++ Layer Four!`."
             }
+            Self::NoTemplateContent => {
+                "Templates must contain a `{{content}}` placeholder for the document to go into."
+            }
+            Self::BadMetadata => {
+                "Metadata must be written as `[meta key=value ...]` or `[meta key=\"some value\" ...]`, \
+                where the key is one of title, description, date, or tags."
+            }
+            Self::BadMetadataDate => "Dates must be written as YYYY-MM-DD.",
+            Self::IncludeWithoutPath => {
+                "`[include ...]` can only be used in documents that were read from a file."
+            }
+            Self::IncludeNotFound => "Included file could not be read.",
+            Self::IncludeCycle => "File ends up including itself, either directly or through other files.",
+            Self::NoLinkTarget => {
+                "Links and images need an address, as in `[link href text]` or `[img src alt]`."
+            }
+            Self::UnsafeLinkTarget => {
+                "Addresses can't run scripts, so `javascript:` and `vbscript:` are not allowed, \
+                and `data:` only for images."
+            }
+            Self::MissingAltText => {
+                "Images should describe themselves for screen readers, as in `[img src alt]`."
+            }
+            Self::BadTableRow => "Table rows must start with a `|`, as in `| one | two |`.",
+            Self::BadAttribute => {
+                "Attributes must be written as `[name#id.class key=value ...]` or `[name {key=\"some value\"} ...]`, \
+                and event handlers like `onclick` or `javascript:` addresses are not allowed."
+            }
+            Self::UnclosedComment => "Opening `%{` must be matched with a closing `}%`.",
+            Self::BadFootnoteRef => {
+                "Footnote references are written as `[^id]`, where the id has no spaces in it."
+            }
+            Self::NoFootnoteId => "Footnotes need an id, as in `[footnote id text]`.",
+            Self::UndefinedFootnote => {
+                "Footnote reference has no matching `[footnote id ...]` anywhere in the document."
+            }
+            Self::DuplicateFootnote => "Another footnote already uses this id.",
+            Self::UnusedFootnote => "Footnote is never referenced with `[^id]`, so it is left out.",
+            Self::BadVariable => {
+                "Variables are defined as `[let name value]` and used as `{{name}}`, \
+                where the name is made of letters, digits, `-` and `_`."
+            }
+            Self::UndefinedVariable => {
+                "Variable is not defined, either with `[let name value]` before it or in `osml.ron`."
+            }
+            Self::BadMacro => {
+                "Macros are defined as `[macro name params...` with the template on the lines below, \
+                up to the closing `]`. Names of built-in blocks can't be used."
+            }
+            Self::MacroArguments => {
+                "Macro was given the wrong number of arguments. \
+                Every parameter but the last takes a word, the last one takes the rest of the block."
+            }
+            Self::MacroRecursion => {
+                "Macros expand too deeply into each other, most likely a macro ends up using itself."
+            }
+            Self::MacroTooLarge => {
+                "Macros expand into too much text, most likely by using each other over and over."
+            }
+            Self::MacroDefinedHere => "The macro is defined here.",
             Self::OtherError(error) => error.as_str(),
         }
         .to_string()
     }
 }
 
+//  Names every delimiter for `format`, in case more than one toggles it.
+fn unclosed_message(ctx: &Context, format: Format) -> String {
+    let mut delimiters: Vec<char> = ctx
+        .delimiters
+        .iter()
+        .filter(|&(_, &f)| f == format)
+        .map(|(&c, _)| c)
+        .collect();
+    delimiters.sort_unstable();
+    let list = |prefix: &str| {
+        delimiters
+            .iter()
+            .map(|c| format!("`{}{}`", prefix, c))
+            .collect::<Vec<_>>()
+            .join(" or ")
+    };
+    let open = list("");
+    format!(
+        "Opening {open} must be matched with a closing {open}. Or, you meant to escape the {open} with {}.",
+        list("\\")
+    )
+}
+
 type Result<T> = std::result::Result<T, Error>;
 
-pub fn parse(s: String, ctx: Context) -> Result<String> {
-    //  <Boring HTML Stuff>
-    let mut output = "<html><head></head><body>".to_string();
-    let lines: Vec<Vec<char>> = s.split('\n').map(|s| s.chars().collect()).collect();
+//  Renders to HTML and places the result into `ctx.template`.
+pub fn parse(s: String, mut ctx: Context) -> Result<String> {
+    let document = parse_document(&s, &mut ctx)?;
+    let content = HtmlRenderer.render(&document);
+    Ok(ctx
+        .template
+        .unwrap_or_default()
+        .apply(&content, &document.meta))
+}
+
+//  Holds everything that stays the same while a document is being parsed.
+pub struct Parser<'a> {
+    pub lines: Vec<Vec<char>>,
+    pub ctx: &'a mut Context,
+    recovering: bool,
+    errors: Vec<Error>,
+    warnings: Vec<Error>,
+    meta: Metadata,
+    //  Where the lines came from, see `Context::path`.
+    path: Option<PathBuf>,
+    //  `path` along with every file that included it, canonicalized.
+    include_stack: Vec<PathBuf>,
+    includes: Vec<PathBuf>,
+    //  Set while parsing the text of a link or image, where addresses aren't turned into links.
+    in_link: bool,
+    //  Notes and references along with the included file they're in, if any, see `resolve_footnotes`.
+    footnotes: Vec<(Footnote, Option<PathBuf>)>,
+    footnote_refs: Vec<(String, Span, Option<PathBuf>)>,
+    //  Starts out as `Context::variables`, included files share it with the file including them.
+    variables: HashMap<String, String>,
+    //  `[macro ...]` definitions, shared like `variables` with included files and expansions.
+    macros: HashMap<String, Macro>,
+    //  How many macro expansions deep this parser is, see `MACRO_DEPTH_LIMIT`.
+    macro_depth: usize,
+    //  Characters that macros have expanded into so far, shared like `macros`, see `MACRO_TEXT_LIMIT`.
+    macro_text: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(s: &str, ctx: &'a mut Context, recovering: bool) -> Self {
+        Parser {
+            lines: s.split('\n').map(|s| s.chars().collect()).collect(),
+            recovering,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            meta: Metadata::default(),
+            path: ctx.path.clone(),
+            include_stack: ctx
+                .path
+                .iter()
+                .map(|path| std::fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+                .collect(),
+            includes: Vec::new(),
+            in_link: false,
+            footnotes: Vec::new(),
+            footnote_refs: Vec::new(),
+            variables: ctx.variables.clone(),
+            macros: HashMap::new(),
+            macro_depth: 0,
+            macro_text: 0,
+            ctx,
+        }
+    }
+
+    //  Throws `error` unless recovering, in which case it is collected and parsing goes on.
+    //  Callers are expected to leave the parser in a sensible state after this returns.
+    pub fn recover(&mut self, error: Error) -> Result<()> {
+        if !self.recovering {
+            return Err(error);
+        }
+        self.errors.push(error);
+        Ok(())
+    }
+
+    //  Warnings never stop parsing, they end up in `Document::warnings`.
+    pub fn warn(&mut self, warning: Error) {
+        self.warnings.push(warning);
+    }
+}
+
+//  Stops at the first error.
+pub fn parse_document(s: &str, ctx: &mut Context) -> Result<Document> {
+    let mut parser = Parser::new(s, ctx, false);
+    let mut document = Document::default();
+    parse_blocks(&mut parser, &mut document.children)?;
+    resolve_footnotes(&mut parser, &mut document)?;
+    resolve_headings(&mut document);
+    document.meta = std::mem::take(&mut parser.meta);
+    document.includes = std::mem::take(&mut parser.includes);
+    document.warnings = std::mem::take(&mut parser.warnings);
+    finish_plugins(&mut parser, &mut document)?;
+    Ok(document)
+}
+
+//  Keeps going after errors, returning every error found along with whatever could be parsed.
+pub fn parse_document_recover(s: &str, ctx: &mut Context) -> (Document, Vec<Error>) {
+    let mut parser = Parser::new(s, ctx, true);
+    let mut document = Document::default();
+    if let Err(e) = parse_blocks(&mut parser, &mut document.children) {
+        parser.errors.push(e);
+    }
+    if let Err(e) = resolve_footnotes(&mut parser, &mut document) {
+        parser.errors.push(e);
+    }
+    resolve_headings(&mut document);
+    document.meta = std::mem::take(&mut parser.meta);
+    document.includes = std::mem::take(&mut parser.includes);
+    document.warnings = std::mem::take(&mut parser.warnings);
+    if let Err(e) = finish_plugins(&mut parser, &mut document) {
+        parser.errors.push(e);
+    }
+    (document, parser.errors)
+}
+
+//  Plugins are finished in order of their names, so that the output stays the same between runs.
+fn finish_plugins(p: &mut Parser, document: &mut Document) -> Result<()> {
+    let mut names: Vec<String> = p.ctx.plugins.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        let res = p.ctx.plugins.get_mut(&name).unwrap().finish(document);
+        if let Err(e) = res {
+            p.recover(e)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_blocks(p: &mut Parser, output: &mut Vec<Node>) -> Result<()> {
     let mut line = 0;
     let mut pos = 0;
     loop {
         //  jmp block_start     ; Find the block start character '['
-        for vline in lines.iter().skip(line) {
-            let mut found = false;
-            let start_pos = pos;
-            for idx in start_pos..vline.len() {
-                let &c = vline.get(idx).unwrap();
+        let mut found = false;
+        'lines: while line < p.lines.len() {
+            while let Some(&c) = p.lines[line].get(pos) {
+                if let Some((nline, npos)) = skip_comment(p, line, pos)? {
+                    (line, pos) = (nline, npos);
+                    continue 'lines;
+                }
                 pos += 1;
                 if c == '[' {
                     found = true;
                     break;
                 }
                 if !is_whitespace(c) {
-                    Err(Error::abs(line, ErrorType::ExpectedBlockStart))?;
+                    //  Skip the rest of the stray text.
+                    let start = Position::new(line, pos - 1);
+                    while p.lines[line].get(pos).is_some_and(|&c| c != '[') {
+                        pos += 1;
+                    }
+                    p.recover(Error::span(
+                        Span::new(start, Position::new(line, pos)),
+                        ErrorType::ExpectedBlockStart,
+                    ))?;
+                    break;
                 }
             }
             if found {
@@ -144,31 +468,14 @@ pub fn parse(s: String, ctx: Context) -> Result<String> {
             line += 1;
             pos = 0;
         }
+        if !found {
+            break;
+        }
 
         //  The fun part: parsing the block!
-        match parse_block(&lines, line, pos, output, &ctx) {
-            Ok((nline, npos, noutput)) => {
-                line = nline;
-                pos = npos;
-                output = noutput;
-            }
-            Err(Error {
-                error: ErrorType::UnexpectedEnd(noutput),
-                ..
-            }) => {
-                output = noutput;
-                break;
-            }
-            e @ Err(_) => {
-                e?;
-                unreachable!("`e` always throws.")
-            }
-        }
+        (line, pos) = parse_block(p, line, pos, output)?;
     }
-
-    //  </Boring HTML Stuff>
-    output = output + "</body></html>";
-    Ok(output)
+    Ok(())
 }
 
 const fn is_whitespace(c: char) -> bool {
@@ -176,319 +483,604 @@ const fn is_whitespace(c: char) -> bool {
 }
 
 const fn is_valid_ch(c: char) -> bool {
-    let c = c as u8;
-    (c >= b'A' && c <= b'Z') || (c >= b'a' && c <= b'z') || c == b'_'
+    c.is_ascii_alphabetic() || c == '_'
 }
 
+//  Characters that lose their meaning when preceded by a `\`.
+const ESCAPABLE: [char; 13] = [
+    '\\', '*', '/', '_', '~', '[', ']', '+', '=', '|', '%', ':', '{',
+];
+
 //  Expects character after '['
 pub fn parse_block(
-    lines: &Vec<Vec<char>>,
+    p: &mut Parser,
     mut line: Line,
     mut pos: Pos,
-    mut output: String,
-    ctx: &Context,
-) -> Result<(Line, Pos, String)> {
-    let vline = lines
-        .get(line)
-        .ok_or(Error::null(ErrorType::UnexpectedEnd(output.clone())))?;
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let start = Position::new(line, pos.saturating_sub(1));
+
+    //  `[^id]` has no name at all.
+    if p.lines.get(line).and_then(|vline| vline.get(pos)) == Some(&'^') {
+        return parse_footnote_ref(p, line, pos, start, output);
+    }
 
     //  Nice to meet you what's your name?
-    let name_start_line = line;
     let mut name = String::new();
-    let mut no_name_end = Err(Error::abs(line, ErrorType::BlockNameNoEnd));
-    let line_len = vline.len();
-    for &(mut c) in vline.iter().skip(pos) {
+    while let Some(&c) = p.lines.get(line).and_then(|vline| vline.get(pos)) {
         //  Hack to get `[section]` to compile.
-        if c == ']' {
-            no_name_end = Ok(());
+        //  The rest are where attributes start, see `parse_attributes`.
+        if c == ']' || ((c == '#' || c == '.' || c == '{') && !name.is_empty()) {
             break;
         }
         pos += 1;
-        if line_len == pos && !is_whitespace(c) {
-            name.push(c);
-            c = ' ';
-        }
         if is_whitespace(c) {
-            no_name_end = Ok(());
             break;
         }
         if !is_valid_ch(c) {
-            Err(Error::abs(name_start_line, ErrorType::BadBlockName))?
+            //  The bad character is left out of the name.
+            p.recover(Error::at(
+                Position::new(line, pos - 1),
+                ErrorType::BadBlockName,
+            ))?;
+            continue;
         }
         name.push(c);
     }
-    no_name_end?;
 
-    //  Look for a plugin to do the job or fall back to text parsing.
-    if let Some(f) = ctx.plugins.get(&name) {
-        f(lines, line, pos, output, ctx)
-    } else {
-        output = format!("{}<div class='{}'>", output, name);
-        let mut last_list_was_ordered = None;
-        let start_line = line;
-        loop {
-            let (done, nline, npos, noutput, nlast_list_was_ordered) = parse_text_line(
-                lines,
-                line,
-                pos,
-                output,
-                ctx,
-                true,
-                start_line,
-                last_list_was_ordered,
-            )?;
-            line = nline;
-            pos = npos;
-            output = noutput;
-            last_list_was_ordered = nlast_list_was_ordered;
-            if done {
-                break;
-            }
+    //  Reserved, metadata describes the document rather than being part of it.
+    if name == "meta" {
+        return parse_meta(p, line, pos, start);
+    }
+    if name == "include" {
+        return parse_include(p, line, pos, start, output);
+    }
+    if name == "footnote" {
+        return parse_footnote(p, line, pos, start);
+    }
+    if name == "let" {
+        return parse_let(p, line, pos, start);
+    }
+    if name == "macro" {
+        return parse_macro(p, line, pos, start);
+    }
+    if p.macros.contains_key(&name) {
+        return parse_macro_call(p, line, pos, start, &name, output);
+    }
+
+    let attributes;
+    if name == "code" {
+        (attributes, line, pos) = parse_code_attributes(p, line, pos)?;
+        return parse_code(p, line, pos, start, attributes, output);
+    }
+    (attributes, line, pos) = parse_attributes(p, line, pos)?;
+    match name.as_str() {
+        "link" => return parse_link(p, line, pos, start, attributes, output),
+        "img" => return parse_image(p, line, pos, start, attributes, output),
+        "table" => return parse_table(p, line, pos, start, attributes, output),
+        _ => {}
+    }
+
+    //  Give a plugin the chance to do the job or fall back to a plain block.
+    if let Some(plugin) = p.ctx.plugins.get_mut(&name) {
+        if let Err(e) = plugin.open(&name, start) {
+            p.recover(e)?;
         }
-        output = output + "</div>";
-        Ok((line, pos, output))
     }
+    let children;
+    (line, pos, children) = parse_block_body(p, line, pos, start)?;
+    let children = match p.ctx.legacy_breaks {
+        true => children,
+        false => group_paragraphs(children),
+    };
+    let block = Block {
+        element: p.ctx.elements.get(&name).cloned(),
+        name,
+        attributes,
+        children,
+        span: Span::new(start, Position::new(line, pos)),
+    };
+    match p.ctx.plugins.get_mut(&block.name) {
+        Some(plugin) => match plugin.render(block) {
+            Ok(nodes) => output.extend(nodes),
+            Err(e) => p.recover(e)?,
+        },
+        None => output.push(Node::Block(block)),
+    }
+    Ok((line, pos))
 }
 
-//  Additionally returns whether a genuine ']' was found.
-pub fn parse_text_line(
-    lines: &Vec<Vec<char>>,
+//  Parses everything up to and including the block's closing ']'.
+//  `start` is the position of the block's opening '['.
+pub fn parse_block_body(
+    p: &mut Parser,
     mut line: Line,
     mut pos: Pos,
-    mut output: String,
-    ctx: &Context,
-    allow_lists: bool,
-    start_line: Line,
-    last_list_was_ordered: Option<bool>,
-) -> Result<(bool, Line, Pos, String, Option<bool>)> {
-    if let Some(is_ordered) = last_list_was_ordered {
-        output = parse_close_list(lines, line, pos, output, is_ordered);
-    }
-    let vline = lines
-        .get(line)
-        .ok_or(Error::abs(start_line, ErrorType::BlockNoEnd))?;
-    let mut bold = None;
-    let mut italic = None;
-    let mut underline = None;
-    let mut strikethrough = None;
-    let check_format = |&bold, &italic, &underline, &strikethrough| -> Result<()> {
-        if let &Some(line) = &bold {
-            Err(Error::abs(line, ErrorType::UnclosedBold))?
+    start: Position,
+) -> Result<(Line, Pos, Vec<Node>)> {
+    let mut children = Vec::new();
+    let mut inline = InlineBuilder::default();
+    loop {
+        let (done, nline, npos) = parse_text_line(
+            p,
+            line,
+            pos,
+            &mut children,
+            &mut inline,
+            TextMode::Block,
+            start,
+        )?;
+        line = nline;
+        pos = npos;
+        if done {
+            break;
         }
-        if let &Some(line) = &italic {
-            Err(Error::abs(line, ErrorType::UnclosedItalic))?
+    }
+    Ok((line, pos, children))
+}
+
+//  Collects inline content while keeping track of open formatting, which carries over between the lines of a paragraph.
+//  Formatting never contains blocks, so spans are closed before a block and reopened after it.
+#[derive(Default)]
+pub struct InlineBuilder {
+    text: String,
+    text_start: Position,
+    formats: Vec<(Format, Position, Vec<Inline>)>,
+}
+
+impl InlineBuilder {
+    fn is_open(&self, format: Format) -> bool {
+        self.formats.iter().any(|&(open, _, _)| open == format)
+    }
+
+    fn push(&mut self, c: char, at: Position) {
+        if self.text.is_empty() {
+            self.text_start = at;
         }
-        if let &Some(line) = &underline {
-            Err(Error::abs(line, ErrorType::UnclosedUnderline))?
+        self.text.push(c);
+    }
+
+    fn flush(&mut self, at: Position, output: &mut Vec<Node>) {
+        if self.text.is_empty() {
+            return;
         }
-        if let &Some(line) = &strikethrough {
-            Err(Error::abs(line, ErrorType::UnclosedStrikethrough))?
+        let text = std::mem::take(&mut self.text);
+        self.emit(
+            Inline::Text {
+                text,
+                span: Span::new(self.text_start, at),
+            },
+            output,
+        );
+    }
+
+    fn emit(&mut self, inline: Inline, output: &mut Vec<Node>) {
+        let siblings = match self.formats.last_mut() {
+            Some((_, _, children)) => children,
+            None => {
+                if let (
+                    Some(Node::Inline(Inline::Text { text, span })),
+                    Inline::Text {
+                        text: next,
+                        span: next_span,
+                    },
+                ) = (output.last_mut(), &inline)
+                {
+                    text.push_str(next);
+                    span.end = next_span.end;
+                } else {
+                    output.push(Node::Inline(inline));
+                }
+                return;
+            }
+        };
+        siblings.push(inline);
+    }
+
+    //  Formatting with nothing in it is dropped, like one closed right after being reopened.
+    fn close_top(&mut self, end: Position, output: &mut Vec<Node>) {
+        if let Some((format, start, children)) = self.formats.pop() {
+            if children.is_empty() {
+                return;
+            }
+            self.emit(
+                Inline::Format {
+                    format,
+                    children,
+                    span: Span::new(start, end),
+                },
+                output,
+            );
         }
-        Ok(())
-    };
-    let maybe_set = |var: &mut Option<Line>, output: &mut String, line, c| {
-        if var.is_some() {
-            *output = format!("{}</{}>", output, c);
-            *var = None
+    }
+
+    fn toggle(&mut self, format: Format, at: Position, output: &mut Vec<Node>) {
+        self.flush(at, output);
+        let end = Position::new(at.line, at.pos + 1);
+        if let Some(idx) = self.formats.iter().position(|(f, _, _)| *f == format) {
+            //  Close whatever was opened inside of `format` and open it back up afterwards.
+            let reopen = self.close_from(idx, end, output);
+            self.reopen(reopen[1..].to_vec(), end);
         } else {
-            *output = format!("{}<{}>", output, c);
-            *var = Some(line)
+            self.formats.push((format, at, Vec::new()));
         }
-    };
+    }
+
+    //  Returns the formats that were closed, outermost first.
+    fn close_from(&mut self, idx: usize, end: Position, output: &mut Vec<Node>) -> Vec<Format> {
+        self.flush(end, output);
+        let closed = self.formats[idx..].iter().map(|(f, _, _)| *f).collect();
+        while self.formats.len() > idx {
+            self.close_top(end, output);
+        }
+        closed
+    }
+
+    fn reopen(&mut self, formats: Vec<Format>, at: Position) {
+        formats
+            .into_iter()
+            .for_each(|format| self.formats.push((format, at, Vec::new())));
+    }
+
+    //  Errors if any formatting was left open, otherwise it gets closed at `end`.
+    fn finish(&mut self, p: &mut Parser, end: Position, output: &mut Vec<Node>) -> Result<()> {
+        for &(format, start, _) in self.formats.iter() {
+            p.recover(Error::at(
+                start,
+                match format {
+                    Format::Bold => ErrorType::UnclosedBold,
+                    Format::Italic => ErrorType::UnclosedItalic,
+                    Format::Underline => ErrorType::UnclosedUnderline,
+                    Format::Strikethrough => ErrorType::UnclosedStrikethrough,
+                },
+            ))?;
+        }
+        self.close_from(0, end, output);
+        Ok(())
+    }
+}
+
+//  Where a line of text sits, which decides what ends it and whether lists can start in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    Block,
+    ListItem,
+    //  Ends at a `|` as well, `+` and `=` are always text.
+    Cell,
+}
+
+//  Additionally returns whether a genuine ']', or for cells a '|', was found.
+//  When recovering, running out of lines ends the block as if it was found.
+pub fn parse_text_line(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+    output: &mut Vec<Node>,
+    inline: &mut InlineBuilder,
+    mode: TextMode,
+    start: Position,
+) -> Result<(bool, Line, Pos)> {
+    if line >= p.lines.len() {
+        inline.finish(p, Position::new(line, 0), output)?;
+        p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+        return Ok((true, line, 0));
+    }
     let mut line_first_valid_ch = true;
-    //  (last character, last last character);
-    let mut triple_last_c = (' ', ' ', ' ');
-    while let Some(&c) = vline.get(pos) {
+    let mut escaped = false;
+    let mut last_c = ' ';
+    //  A space right after a nested block is kept, one at the end of the line isn't added.
+    let mut after_block = false;
+    while let Some(&c) = p.lines[line].get(pos) {
+        let at = Position::new(line, pos);
         match c {
-            '[' if triple_last_c.0 != '\\' => {
-                let (nline, npos, noutput) = parse_block(lines, line, pos + 1, output, ctx)?;
-                line = nline;
+            _ if escaped => {
+                if !ESCAPABLE.contains(&c) && !p.ctx.delimiters.contains_key(&c) {
+                    //  Drop the backslash and keep the character.
+                    p.recover(Error::span(
+                        Span::new(Position::new(line, pos - 1), Position::new(line, pos + 1)),
+                        ErrorType::StrayBackslash,
+                    ))?;
+                }
+                inline.push(c, at);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            '{' if p.lines[line].get(pos + 1) == Some(&'{') => {
+                let (text, npos) = parse_interpolation(p, line, pos)?;
+                text.chars().for_each(|c| inline.push(c, at));
                 pos = npos;
-                output = noutput;
+                last_c = '}';
+                line_first_valid_ch = false;
+                after_block = false;
                 continue;
             }
-            ']' if triple_last_c.0 != '\\' => {
-                check_format(&bold, &italic, &underline, &strikethrough)?;
-                return Ok((true, line, pos+1, output, None));
-            }
-            '+' if line_first_valid_ch => {
-                if !allow_lists {
-                    Err(Error::abs(line, ErrorType::RecursiveList))?
+            '%' if matches!(p.lines[line].get(pos + 1), Some('%' | '{')) => {
+                let (nline, npos) = skip_comment(p, line, pos)?.unwrap();
+                if nline == line {
+                    pos = npos;
+                    continue;
+                }
+                //  A comment over multiple lines stands in for a line break,
+                //  the rest of the line it closes on carries on this one rather than starting a new one.
+                if !is_whitespace(last_c) {
+                    inline.push(' ', at);
+                    last_c = ' ';
                 }
-                return parse_open_list(lines, line, pos, output, ctx, false, start_line);
+                (line, pos) = (nline, npos);
+                after_block = false;
+                continue;
             }
-            '=' if line_first_valid_ch => {
-                if !allow_lists {
-                    Err(Error::abs(line, ErrorType::RecursiveList))?
+            '[' => {
+                let reopen = inline.close_from(0, at, output);
+                (line, pos) = parse_block(p, line, pos + 1, output)?;
+                if line >= p.lines.len() {
+                    p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+                    return Ok((true, line, 0));
                 }
-                return parse_open_list(lines, line, pos, output, ctx, true, start_line);
+                inline.reopen(reopen, Position::new(line, pos));
+                line_first_valid_ch = false;
+                after_block = true;
+                continue;
             }
-            '*' if triple_last_c.0 != '\\'
-                || (triple_last_c.0 == '\\' && triple_last_c.1 == '\\') =>
-            {
-                maybe_set(&mut bold, &mut output, line, 'b')
+            ']' => {
+                inline.finish(p, at, output)?;
+                return Ok((true, line, pos + 1));
             }
-            '/' if triple_last_c.0 != '\\'
-                || (triple_last_c.0 == '\\' && triple_last_c.1 == '\\') =>
-            {
-                maybe_set(&mut italic, &mut output, line, 'i')
+            '|' if mode == TextMode::Cell => {
+                inline.finish(p, at, output)?;
+                return Ok((true, line, pos + 1));
             }
-            '_' if triple_last_c.0 != '\\'
-                || (triple_last_c.0 == '\\' && triple_last_c.1 == '\\') =>
-            {
-                maybe_set(&mut underline, &mut output, line, 'u')
+            '+' | '=' if line_first_valid_ch && mode == TextMode::Cell => inline.push(c, at),
+            '+' | '=' if line_first_valid_ch && mode == TextMode::ListItem => {
+                //  Treated as text from here on.
+                p.recover(Error::at(at, ErrorType::RecursiveList))?;
+                inline.push(c, at);
+            }
+            '+' | '=' if line_first_valid_ch => {
+                inline.finish(p, at, output)?;
+                return parse_open_list(p, line, pos, output, c == '=', start);
             }
-            '~' if triple_last_c.0 != '\\'
-                || (triple_last_c.0 == '\\' && triple_last_c.1 == '\\') =>
+            ':' if line_first_valid_ch
+                && mode == TextMode::Block
+                && starts_definition(p, line, pos) =>
             {
-                maybe_set(&mut strikethrough, &mut output, line, 's')
+                inline.finish(p, at, output)?;
+                return parse_definition(p, line, pos, output, start);
             }
-            '\\' if triple_last_c.0 == '\\' => output.push(c),
-            '\\' => {}
-            ' ' if !is_whitespace(triple_last_c.0) => output.push(c),
-            ' ' => {}
-            _ if !['*', '/', '_', '~'].contains(&c) && triple_last_c.0 == '\\' => {
-                Err(Error::abs(line, ErrorType::StrayBackslash))?
+            _ if !p.in_link && is_autolink_start(&p.lines[line], pos) => {
+                if let Some(len) = autolink_len(p, line, pos, inline) {
+                    let end = Position::new(line, pos + len);
+                    let reopen = inline.close_from(0, at, output);
+                    output.push(Node::Link(Link {
+                        attributes: Vec::new(),
+                        href: p.lines[line][pos..pos + len].iter().collect(),
+                        children: Vec::new(),
+                        span: Span::new(at, end),
+                    }));
+                    inline.reopen(reopen, end);
+                    pos += len;
+                    last_c = p.lines[line][pos - 1];
+                    line_first_valid_ch = false;
+                    after_block = false;
+                    continue;
+                }
+                inline.push(c, at);
+            }
+            '/' if is_path_slash(&p.lines[line], pos) => inline.push(c, at),
+            _ if p.ctx.delimiters.contains_key(&c) => {
+                let format = p.ctx.delimiters[&c];
+                inline.toggle(format, at, output)
             }
-            _ => {
-                output.push(c);
+            _ if is_whitespace(c) => {
+                if !is_whitespace(last_c) || after_block {
+                    inline.push(' ', at);
+                }
             }
+            _ => inline.push(c, at),
         }
+        after_block = false;
         pos += 1;
-        if c == '\t' {
-            continue;
-        }
         if !is_whitespace(c) {
             line_first_valid_ch = false;
         }
-        triple_last_c = (c, triple_last_c.0, triple_last_c.1);
+        last_c = c;
     }
-    pos = 0;
-    line += 1;
-    if vline.len() == 0 {
-        output.push_str("<br><br>");
-    } else if !is_whitespace(triple_last_c.0) {
-        output.push(' ');
+    if escaped {
+        p.recover(Error::at(
+            Position::new(line, pos - 1),
+            ErrorType::StrayBackslash,
+        ))?;
     }
-    check_format(&bold, &italic, &underline, &strikethrough)?;
-    return Ok((false, line, pos, output, None));
+    let end = Position::new(line, pos);
+    //  Formatting only has to be closed by the end of the paragraph or list item, see `parse_continuation`.
+    if p.lines[line].is_empty() {
+        inline.finish(p, end, output)?;
+        output.push(Node::Break(Span::new(end, end)));
+    } else {
+        if !is_whitespace(last_c) {
+            inline.push(' ', end);
+        }
+        if mode == TextMode::Cell {
+            inline.finish(p, end, output)?;
+        }
+    }
+    Ok((false, line + 1, 0))
 }
 
-fn parse_list_determine_depth(
-    lines: &Vec<Vec<char>>,
+//  Expects the first list character.
+pub fn parse_open_list(
+    p: &mut Parser,
     line: Line,
     mut pos: Pos,
+    output: &mut Vec<Node>,
     is_ordered: bool,
-) -> Option<(usize, Pos)> {
+    block_start: Position,
+) -> Result<(bool, Line, Pos)> {
     let listc = if is_ordered { '=' } else { '+' };
-    let vline = lines.get(line)?;
+    let start = Position::new(line, pos);
     let mut depth = 0;
-    let mut start = false;
-    while let Some(&c) = vline.get(pos) {
+    while p.lines[line].get(pos) == Some(&listc) {
+        depth += 1;
         pos += 1;
-        //  Walk to the first occurance of `listc`.
-        if c == listc {
-            start = true;
-        }
-        if !start {
-            continue;
-        }
+    }
 
-        //  Increment depth until you run out of `listc`.
-        depth += 1;
-        if c != listc {
-            break;
+    //  Items can nest one level deeper than the list right before them, whatever its kind.
+    if let Some(Node::List(list)) = output.last() {
+        let max_depth = list.last_depth() + 1;
+        if depth > max_depth {
+            //  Pretend it was nested one level deeper.
+            depth = max_depth;
+            p.recover(Error::span(
+                Span::new(start, Position::new(line, pos)),
+                ErrorType::InvalidListDepth,
+            ))?;
         }
     }
-    if depth == 0 {
-        None
-    } else {
-        Some((depth, pos))
+
+    //  `+ [x] done` and `+ [ ] to do` are task items.
+    let text = p.lines[line][pos..]
+        .iter()
+        .position(|&c| !is_whitespace(c))
+        .map_or(pos, |i| pos + i);
+    let checked = match p.lines[line].get(text..text + 3) {
+        Some(['[', c @ (' ' | 'x' | 'X'), ']'])
+            if p.lines[line]
+                .get(text + 3)
+                .is_none_or(|&c| is_whitespace(c)) =>
+        {
+            pos = text + 3;
+            Some(*c != ' ')
+        }
+        _ => None,
+    };
+
+    let mut children = Vec::new();
+    let mut inline = InlineBuilder::default();
+    let (mut done, mut nline, mut npos) = parse_text_line(
+        p,
+        line,
+        pos,
+        &mut children,
+        &mut inline,
+        TextMode::ListItem,
+        block_start,
+    )?;
+    if !done {
+        (done, nline, npos) = parse_continuation(
+            p,
+            nline,
+            npos,
+            start.pos,
+            &mut children,
+            &mut inline,
+            block_start,
+        )?;
     }
+    let item = ListItem {
+        checked,
+        children,
+        span: Span::new(start, item_end(p, done, nline, npos)),
+    };
+
+    insert_list_item(output, 1, depth, is_ordered, item);
+    Ok((done, nline, npos))
 }
 
-pub fn parse_open_list(
-    lines: &Vec<Vec<char>>,
+//  Lines indented past `column` carry on the item or definition above them,
+//  up until an empty line, a line indented less, or another list item or term.
+//  Formatting can go on over these lines, it has to be closed by the time the item ends.
+pub fn parse_continuation(
+    p: &mut Parser,
     mut line: Line,
     mut pos: Pos,
-    mut output: String,
-    ctx: &Context,
-    is_ordered: bool,
-    start_line: Line,
-) -> Result<(bool, Line, Pos, String, Option<bool>)> {
-    enum ListManipulation {
-        Push,
-        Pop,
-    }
-
-    let depth;
-    let mut manipulations = vec![];
-    if let Some((ndepth, npos)) = parse_list_determine_depth(lines, line, pos, is_ordered) {
-        depth = ndepth;
-        pos = npos;
-    } else {
-        unreachable!("In this case, `parse_list` should not have been called.")
-    }
-    if let Some((last_depth, _)) = parse_list_determine_depth(lines, line - 1, 0, is_ordered) {
-        if depth - 1 == last_depth {
-            manipulations.push(ListManipulation::Push);
-        } else if depth < last_depth {
-            (0..last_depth - depth)
-                .into_iter()
-                .for_each(|_| manipulations.push(ListManipulation::Pop));
-        } else if depth != last_depth {
-            Err(Error::abs(line, ErrorType::InvalidListDepth))?
+    column: Pos,
+    output: &mut Vec<Node>,
+    inline: &mut InlineBuilder,
+    block_start: Position,
+) -> Result<(bool, Line, Pos)> {
+    //  Lines of text always leave off at the start of the next line, anything else ends the item.
+    while pos == 0 && line < p.lines.len() {
+        let vline = &p.lines[line];
+        let indent = vline.iter().take_while(|&&c| is_whitespace(c)).count();
+        if indent <= column || indent == vline.len() || matches!(vline[indent], '+' | '=' | ':') {
+            break;
         }
-    } else {
-        (0..depth - 1)
-            .into_iter()
-            .for_each(|_| manipulations.push(ListManipulation::Push));
-    }
-
-    for manipulation in manipulations {
-        match manipulation {
-            ListManipulation::Push => {
-                output.push_str(if is_ordered { "<ol>" } else { "<ul>" });
-            }
-            ListManipulation::Pop => {
-                output.push_str(if is_ordered { "</ol>" } else { "</ul>" });
-            }
+        let (done, nline, npos) = parse_text_line(
+            p,
+            line,
+            indent,
+            output,
+            inline,
+            TextMode::ListItem,
+            block_start,
+        )?;
+        if done {
+            return Ok((true, nline, npos));
         }
+        (line, pos) = (nline, npos);
     }
+    inline.finish(p, item_end(p, false, line, pos), output)?;
+    Ok((false, line, pos))
+}
 
-    output.push_str("<li>");
-    let (done, nline, npos, noutput, _) = parse_text_line(
-        lines,
-        line,
-        pos - 1,
-        output,
-        ctx,
-        false,
-        start_line,
-        None,
-    )?;
-    line = nline;
-    pos = npos;
-    output = noutput;
-    output.push_str("</li>");
-
-    Ok((done, line, pos, output, Some(is_ordered)))
+//  Where an item that parsing left off at `(line, pos)` ends, see `parse_text_line`.
+pub(crate) fn item_end(p: &Parser, done: bool, line: Line, pos: Pos) -> Position {
+    match done || pos > 0 {
+        true => Position::new(line, pos),
+        false => Position::new(line - 1, p.lines[line - 1].len()),
+    }
 }
 
-pub fn parse_close_list(
-    lines: &Vec<Vec<char>>,
-    line: Line,
-    pos: Pos,
-    mut output: String,
+//  `nodes` holds the lists at `list_depth`, the item goes into the last of them when it is of the same kind,
+//  or under the last item when it is deeper. Items without a parent get an empty one.
+pub(crate) fn insert_list_item(
+    nodes: &mut Vec<Node>,
+    list_depth: usize,
+    depth: usize,
     is_ordered: bool,
-) -> String {
-    if let (None, Some((depth, _))) = (
-        parse_list_determine_depth(lines, line, pos, is_ordered),
-        parse_list_determine_depth(lines, line - 1, 0, is_ordered),
-    ) {
-        for _ in 0..depth - 1 {
-            output = output + if is_ordered { "</ol>" } else { "</ul>" };
+    item: ListItem,
+) {
+    let end = item.span.end;
+    if depth <= list_depth {
+        match nodes.last_mut() {
+            Some(Node::List(list)) if list.ordered == is_ordered => {
+                list.span.end = end;
+                list.items.push(item);
+            }
+            _ => nodes.push(Node::List(List {
+                ordered: is_ordered,
+                depth: list_depth,
+                span: item.span,
+                items: vec![item],
+            })),
+        }
+        return;
+    }
+    if !matches!(nodes.last(), Some(Node::List(_))) {
+        nodes.push(Node::List(List {
+            ordered: is_ordered,
+            depth: list_depth,
+            items: Vec::new(),
+            span: item.span,
+        }));
+    }
+    if let Some(Node::List(list)) = nodes.last_mut() {
+        if list.items.is_empty() {
+            list.items.push(ListItem {
+                checked: None,
+                children: Vec::new(),
+                span: item.span,
+            });
         }
+        list.span.end = end;
+        let parent = list.items.last_mut().unwrap();
+        parent.span.end = end;
+        insert_list_item(
+            &mut parent.children,
+            list_depth + 1,
+            depth,
+            is_ordered,
+            item,
+        );
     }
-    output
 }
@@ -0,0 +1,208 @@
+use super::{
+    is_whitespace, parse_block_body, Attributes, Error, ErrorType, Image, Inline, InlineBuilder,
+    Line, Link, Node, Parser, Pos, Position, Result, Span,
+};
+
+//  `[link https://example.com some *text*]`, the text falls back to the address when left out.
+//  Expects the character after the name.
+pub fn parse_link(
+    p: &mut Parser,
+    line: Line,
+    pos: Pos,
+    start: Position,
+    attributes: Attributes,
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let (href, line, pos) = parse_target(p, line, pos, start, false)?;
+    let (line, pos, children) = parse_link_text(p, line, pos, start)?;
+    output.push(Node::Link(Link {
+        attributes,
+        href,
+        children,
+        span: Span::new(start, Position::new(line, pos)),
+    }));
+    Ok((line, pos))
+}
+
+//  `[img cat.png A cat sitting on a mat]`, formatting in the alt text is dropped.
+//  Expects the character after the name.
+pub fn parse_image(
+    p: &mut Parser,
+    line: Line,
+    pos: Pos,
+    start: Position,
+    attributes: Attributes,
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let (src, line, pos) = parse_target(p, line, pos, start, true)?;
+    let (line, pos, children) = parse_link_text(p, line, pos, start)?;
+    let span = Span::new(start, Position::new(line, pos));
+    let mut alt = String::new();
+    plain_text(&children, &mut alt);
+    let alt = Some(alt.trim().to_string()).filter(|alt| !alt.is_empty());
+    if alt.is_none() {
+        p.warn(Error::span(span, ErrorType::MissingAltText));
+    }
+    output.push(Node::Image(Image {
+        attributes,
+        src,
+        alt,
+        span,
+    }));
+    Ok((line, pos))
+}
+
+//  The address up to the next whitespace, along with where the rest of the block starts.
+//  Script addresses are left out, see `is_unsafe_url`.
+fn parse_target(
+    p: &mut Parser,
+    line: Line,
+    mut pos: Pos,
+    start: Position,
+    allow_data: bool,
+) -> Result<(String, Line, Pos)> {
+    let vline = &p.lines[line];
+    let target_start = pos;
+    let mut target: String = vline[pos.min(vline.len())..]
+        .iter()
+        .take_while(|&&c| !is_whitespace(c) && c != ']')
+        .collect();
+    pos += target.chars().count();
+    let span = Span::new(Position::new(line, target_start), Position::new(line, pos));
+    while vline.get(pos).is_some_and(|&c| is_whitespace(c)) {
+        pos += 1;
+    }
+    if target.is_empty() {
+        p.recover(Error::at(start, ErrorType::NoLinkTarget))?;
+    } else if is_unsafe_url(&target, allow_data) {
+        p.recover(Error::span(span, ErrorType::UnsafeLinkTarget))?;
+        target.clear();
+    }
+    Ok((target, line, pos))
+}
+
+//  `javascript:` and friends run when followed, whatever the quoting. `data:` can hold a whole page,
+//  which is only harmless as the source of an image.
+//  Browsers trim whitespace around the address and ignore case and control characters in the scheme,
+//  so this does too.
+pub(crate) fn is_unsafe_url(url: &str, allow_data: bool) -> bool {
+    let url = url.trim_matches(|c: char| c.is_ascii_whitespace() || c.is_control());
+    let Some((scheme, _)) = url.split_once(':') else {
+        return false;
+    };
+    let scheme: String = scheme
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    scheme == "javascript" || scheme == "vbscript" || (scheme == "data" && !allow_data)
+}
+
+//  Links can't be nested, so addresses in the text are left alone.
+fn parse_link_text(
+    p: &mut Parser,
+    line: Line,
+    pos: Pos,
+    start: Position,
+) -> Result<(Line, Pos, Vec<Node>)> {
+    let in_link = std::mem::replace(&mut p.in_link, true);
+    let body = parse_block_body(p, line, pos, start);
+    p.in_link = in_link;
+    body
+}
+
+//  Whether an address like `https://example.com` could start at `pos`, see `autolink_len`.
+pub(crate) fn is_autolink_start(vline: &[char], pos: Pos) -> bool {
+    vline[pos].is_ascii_alphabetic() && (pos == 0 || !vline[pos - 1].is_alphanumeric())
+}
+
+//  The length of the bare address at `pos`, written as `scheme://...`.
+//  Punctuation at the end is left out, and so are delimiters closing formatting that was opened before the address,
+//  so both `(see https://example.com/*a*).` and `*see https://example.com*` work.
+pub(crate) fn autolink_len(
+    p: &Parser,
+    line: Line,
+    pos: Pos,
+    inline: &InlineBuilder,
+) -> Option<usize> {
+    let rest = &p.lines[line][pos..];
+    let scheme = rest
+        .iter()
+        .take_while(|&&c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+        .count();
+    if !rest[scheme..].starts_with(&[':', '/', '/'])
+        || is_unsafe_url(&rest[..scheme + 1].iter().collect::<String>(), false)
+    {
+        return None;
+    }
+    let url = &rest[..rest
+        .iter()
+        .take_while(|&&c| !is_whitespace(c) && !matches!(c, '[' | ']' | '|' | '<' | '>' | '"'))
+        .count()];
+    let mut len = url.len();
+    let mut closed = Vec::new();
+    while len > scheme + 3 {
+        let c = url[len - 1];
+        let unbalanced = c == ')'
+            && url[..len].iter().filter(|&&c| c == '(').count()
+                < url[..len].iter().filter(|&&c| c == ')').count();
+        let closes = p
+            .ctx
+            .delimiters
+            .get(&c)
+            .is_some_and(|&format| inline.is_open(format) && !closed.contains(&format));
+        if closes {
+            closed.push(p.ctx.delimiters[&c]);
+        }
+        if matches!(c, '.' | ',' | ';' | ':' | '!' | '?' | '\'') || unbalanced || closes {
+            len -= 1;
+        } else {
+            break;
+        }
+    }
+    Some(len).filter(|&len| len > scheme + 3)
+}
+
+//  A `/` inside of a word like `src/index.osml`, `and/or` or `/usr/bin` is text rather than italics.
+//  That is the case when any `/` in the word sits between a letter, digit, `.` or `-` and a letter or digit,
+//  counting variables like `{{dir}}/{{file}}` as letters,
+//  or when the word has a `://` in it, as addresses in the text of links do.
+pub(crate) fn is_path_slash(vline: &[char], pos: Pos) -> bool {
+    let is_boundary = |c: &char| is_whitespace(*c) || matches!(c, '[' | ']');
+    let start = vline[..pos]
+        .iter()
+        .rposition(is_boundary)
+        .map_or(0, |i| i + 1);
+    let end = vline[pos..]
+        .iter()
+        .position(is_boundary)
+        .map_or(vline.len(), |i| pos + i);
+    let word = &vline[start..end];
+    word.windows(3).any(|w| w == [':', '/', '/'])
+        || (1..word.len().saturating_sub(1)).any(|i| {
+            word[i] == '/'
+                && (word[i - 1].is_alphanumeric() || matches!(word[i - 1], '.' | '-' | '}'))
+                && (word[i + 1].is_alphanumeric() || word[i + 1] == '{')
+        })
+}
+
+pub(crate) fn plain_text(nodes: &[Node], output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Inline(inline) => plain_inline(inline, output),
+            Node::Block(block) => plain_text(&block.children, output),
+            Node::Link(link) => plain_text(&link.children, output),
+            Node::Break(_) => output.push(' '),
+            _ => {}
+        }
+    }
+}
+
+fn plain_inline(inline: &Inline, output: &mut String) {
+    match inline {
+        Inline::Text { text, .. } => output.push_str(text),
+        Inline::Format { children, .. } => {
+            children.iter().for_each(|c| plain_inline(c, output));
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use super::{
+    is_valid_ch, is_whitespace, key_len, parse_block_body, trim_blank_lines, Error, ErrorType,
+    Line, Location, Node, Parser, Pos, Position, Result, Span,
+};
+use std::mem::take;
+use std::path::PathBuf;
+
+//  How deeply macros can expand into each other, which is also what stops a macro from using itself forever.
+pub const MACRO_DEPTH_LIMIT: usize = 32;
+//  How many characters all expansions of a document can add up to. Macros using each other more than once
+//  grow exponentially within `MACRO_DEPTH_LIMIT`, which would let a small snippet take down the renderer.
+pub const MACRO_TEXT_LIMIT: usize = 1 << 20;
+
+//  Blocks the parser handles itself, a macro can't take their place.
+const RESERVED: [&str; 10] = [
+    "meta", "include", "footnote", "let", "macro", "code", "link", "img", "table", "toc",
+];
+
+//  A template written in OSML, see `parse_macro`.
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub params: Vec<String>,
+    pub template: String,
+    //  Where the `[macro ...]` is, `file` being `None` for documents that weren't read from a file.
+    pub span: Span,
+    pub file: Option<PathBuf>,
+}
+
+//  [macro callout kind body
+//      [aside.{{kind}} *{{kind}}:* {{body}}]
+//  ]
+//  The name and parameters go on the first line, the lines below it up to the closing `]` are the template.
+//  Only text after it can use the macro, a later `[macro ...]` of the same name replaces it.
+//  Expects the character after the name.
+pub fn parse_macro(p: &mut Parser, line: Line, pos: Pos, start: Position) -> Result<(Line, Pos)> {
+    let vline = &p.lines[line];
+    let header = &vline[pos.min(vline.len())..];
+    //  A template always starts on the next line, so there's nothing to define.
+    if let Some(close) = header.iter().position(|&c| c == ']') {
+        let end = pos + close + 1;
+        p.recover(Error::span(
+            Span::new(start, Position::new(line, end)),
+            ErrorType::BadMacro,
+        ))?;
+        return Ok((line, end));
+    }
+    let header: String = header.iter().collect();
+    let mut words = header.split_whitespace().map(str::to_string);
+    let name = words.next().unwrap_or_default();
+    let params: Vec<String> = words.collect();
+
+    let (text, end_line, end_pos) = capture_raw(p, line + 1, 0, start)?;
+    let span = Span::new(start, Position::new(end_line, end_pos));
+    let bad_param = |param: &String| {
+        let chars: Vec<char> = param.chars().collect();
+        key_len(&chars) != chars.len()
+    };
+    if name.is_empty()
+        || !name.chars().all(is_valid_ch)
+        || RESERVED.contains(&name.as_str())
+        || params.iter().any(bad_param)
+        || (1..params.len()).any(|i| params[..i].contains(&params[i]))
+    {
+        p.recover(Error::span(span, ErrorType::BadMacro))?;
+        return Ok((end_line, end_pos));
+    }
+    let definition = Macro {
+        params,
+        template: trim_blank_lines(&text).to_string(),
+        span,
+        file: p.path.clone(),
+    };
+    p.macros.insert(name, definition);
+    Ok((end_line, end_pos))
+}
+
+//  `[callout warning Mind the *gap*.]`, every parameter but the last takes a word and the last takes the rest.
+//  The template is parsed with the arguments in place of `{{param}}`, as if it was written at the call.
+//  Errors inside the expansion point at the call, with a note pointing at the definition.
+//  Expects the character after the name.
+pub fn parse_macro_call(
+    p: &mut Parser,
+    line: Line,
+    mut pos: Pos,
+    start: Position,
+    name: &str,
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let definition = p.macros[name].clone();
+    let mut args = Vec::new();
+    for _ in 1..definition.params.len() {
+        let vline = &p.lines[line];
+        while vline.get(pos).is_some_and(|&c| is_whitespace(c)) {
+            pos += 1;
+        }
+        //  A word can be a whole block, as in `[callout [b x] ...]`.
+        let word_start = pos;
+        let (mut depth, mut escaped) = (0, false);
+        while let Some(&c) = vline.get(pos) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '[' => depth += 1,
+                ']' if depth == 0 => break,
+                ']' => depth -= 1,
+                _ if is_whitespace(c) && depth == 0 => break,
+                _ => {}
+            }
+            pos += 1;
+        }
+        args.push(vline[word_start..pos].iter().collect());
+    }
+    let (rest, end_line, end_pos) = capture_raw(p, line, pos, start)?;
+    let call = Span::new(start, Position::new(end_line, end_pos));
+    let defined_here = Error {
+        file: definition.file.clone(),
+        ..Error::span(definition.span, ErrorType::MacroDefinedHere)
+    };
+    let at_call = |mut e: Error| {
+        //  Errors that already know their file come from an include inside the expansion.
+        if e.file.is_none() {
+            e.location = Location::Span(call);
+            e.note.get_or_insert_with(|| Box::new(defined_here.clone()));
+        }
+        e
+    };
+
+    let rest = rest.trim().to_string();
+    let arguments_fit = match definition.params.len() {
+        0 => rest.is_empty(),
+        _ => !args.iter().any(String::is_empty),
+    };
+    if !arguments_fit {
+        p.recover(at_call(Error::null(ErrorType::MacroArguments)))?;
+        return Ok((end_line, end_pos));
+    }
+    if !definition.params.is_empty() {
+        args.push(rest);
+    }
+    if p.macro_depth >= MACRO_DEPTH_LIMIT {
+        p.recover(at_call(Error::null(ErrorType::MacroRecursion)))?;
+        return Ok((end_line, end_pos));
+    }
+
+    let budget = MACRO_TEXT_LIMIT.saturating_sub(p.macro_text);
+    let Some(mut source) = substitute(&definition.template, &definition.params, &args, budget)
+    else {
+        p.macro_text = MACRO_TEXT_LIMIT;
+        p.recover(at_call(Error::null(ErrorType::MacroTooLarge)))?;
+        return Ok((end_line, end_pos));
+    };
+    p.macro_text += source.len();
+    //  The closing `]` is added back so that the expansion reads like the body of a block.
+    source.push(']');
+    let mut expanded = Parser::new(&source, p.ctx, p.recovering);
+    expanded.path = p.path.clone();
+    expanded.include_stack = p.include_stack.clone();
+    expanded.in_link = p.in_link;
+    expanded.variables = take(&mut p.variables);
+    expanded.macros = take(&mut p.macros);
+    expanded.macro_depth = p.macro_depth + 1;
+    expanded.macro_text = p.macro_text;
+    let res = parse_block_body(&mut expanded, 0, 0, Position::default());
+    let Parser {
+        errors,
+        warnings,
+        includes,
+        footnotes,
+        footnote_refs,
+        variables,
+        macros,
+        macro_text,
+        ..
+    } = expanded;
+    p.variables = variables;
+    p.macros = macros;
+    p.macro_text = macro_text;
+
+    p.errors.extend(errors.into_iter().map(at_call));
+    p.warnings.extend(warnings.into_iter().map(at_call));
+    p.includes.extend(includes);
+    //  Notes and references made by the expansion are only known by where it's called from.
+    p.footnotes
+        .extend(footnotes.into_iter().map(|(mut footnote, file)| {
+            if file.is_none() {
+                footnote.span = call;
+            }
+            (footnote, file)
+        }));
+    p.footnote_refs.extend(
+        footnote_refs
+            .into_iter()
+            .map(|(id, span, file)| (id, if file.is_none() { call } else { span }, file)),
+    );
+    match res {
+        Ok((_, _, children)) => output.extend(children),
+        Err(e) => p.recover(at_call(e))?,
+    }
+    Ok((end_line, end_pos))
+}
+
+//  Everything up to the `]` matching the block at `start`, as written.
+//  Escapes are kept so that the text can be parsed again, but an escaped bracket doesn't count.
+//  Neither do brackets in comments and code, which are copied as they are, see `verbatim_end`.
+fn capture_raw(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+    start: Position,
+) -> Result<(String, Line, Pos)> {
+    let mut text = String::new();
+    let mut depth = 0;
+    let mut escaped = false;
+    let mut verbatim = None;
+    loop {
+        let Some(vline) = p.lines.get(line) else {
+            p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+            return Ok((text, line, 0));
+        };
+        let Some(&c) = vline.get(pos) else {
+            text.push('\n');
+            (line, pos) = (line + 1, 0);
+            continue;
+        };
+        if !escaped && verbatim.is_none_or(|end| (line, pos) >= end) {
+            verbatim = verbatim_end(p, line, pos);
+        }
+        if verbatim.is_some_and(|end| (line, pos) < end) {
+            text.push(c);
+            pos += 1;
+            continue;
+        }
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' if depth == 0 => return Ok((text, line, pos + 1)),
+            ']' => depth -= 1,
+            _ => {}
+        }
+        text.push(c);
+        pos += 1;
+    }
+}
+
+//  Where a comment or `[code ...]` starting at `pos` ends, unless it is never closed.
+fn verbatim_end(p: &Parser, line: Line, pos: Pos) -> Option<(Line, Pos)> {
+    let vline = &p.lines[line];
+    match vline[pos..] {
+        ['%', '%', ..] => Some((line, vline.len())),
+        ['%', '{', ..] => find(p, line, pos + 2, |rest| rest.starts_with(&['}', '%']))
+            .map(|(line, pos)| (line, pos + 2)),
+        ['[', 'c', 'o', 'd', 'e', ..]
+            if vline
+                .get(pos + 5)
+                .is_none_or(|&c| is_whitespace(c) || matches!(c, '#' | '.' | ']')) =>
+        {
+            code_end(p, line, pos + 5)
+        }
+        _ => None,
+    }
+}
+
+//  Skips the attributes the way `parse_code_attributes` does, then finds the end the way `parse_code` does.
+//  Expects the character after the name.
+fn code_end(p: &Parser, line: Line, mut pos: Pos) -> Option<(Line, Pos)> {
+    let vline = &p.lines[line];
+    let at = |pos: Pos| vline.get(pos).copied();
+    if at(pos).is_some_and(is_whitespace) {
+        pos += 1;
+    }
+    let mut shorthand = false;
+    while matches!(at(pos), Some('#' | '.')) {
+        shorthand = true;
+        pos += 1;
+        while at(pos).is_some_and(|c| !is_whitespace(c) && !matches!(c, '#' | '.' | ']' | '{')) {
+            pos += 1;
+        }
+    }
+    if shorthand && at(pos).is_some_and(is_whitespace) {
+        pos += 1;
+    }
+    if vline
+        .get(pos..pos + 5)
+        .is_some_and(|key| key.iter().copied().eq("lang=".chars()))
+    {
+        pos += 5;
+        if at(pos) == Some('"') {
+            pos += 1;
+            let mut escaped = false;
+            while let Some(c) = at(pos) {
+                pos += 1;
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => break,
+                    _ => {}
+                }
+            }
+        } else {
+            while at(pos).is_some_and(|c| !is_whitespace(c) && c != ']' && c != '}') {
+                pos += 1;
+            }
+        }
+        if at(pos).is_some_and(is_whitespace) {
+            pos += 1;
+        }
+    }
+
+    let fence = vline[pos.min(vline.len())..]
+        .iter()
+        .take_while(|&&c| c == '`')
+        .count();
+    if fence > 0 {
+        let closes = |rest: &[char]| {
+            rest.iter().take_while(|&&c| c == '`').count() == fence && rest.get(fence) == Some(&']')
+        };
+        return find(p, line, pos + fence, closes).map(|(line, pos)| (line, pos + fence + 1));
+    }
+    let mut depth = 0;
+    find(p, line, pos, |rest| {
+        match rest[0] {
+            '[' => depth += 1,
+            ']' if depth == 0 => return true,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        false
+    })
+    .map(|(line, pos)| (line, pos + 1))
+}
+
+//  The first position from `(line, pos)` on where `at` holds for the rest of the line.
+fn find(
+    p: &Parser,
+    mut line: Line,
+    mut pos: Pos,
+    mut at: impl FnMut(&[char]) -> bool,
+) -> Option<(Line, Pos)> {
+    loop {
+        let vline = p.lines.get(line)?;
+        if pos >= vline.len() {
+            (line, pos) = (line + 1, 0);
+        } else if at(&vline[pos..]) {
+            return Some((line, pos));
+        } else {
+            pos += 1;
+        }
+    }
+}
+
+//  Replaces `{{param}}` with its argument, any other `{{name}}` is left for `parse_interpolation`.
+//  `None` once the result would be longer than `limit`.
+fn substitute(template: &str, params: &[String], args: &[String], limit: usize) -> Option<String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find("{{") {
+        let escaped = rest[..i].ends_with('\\');
+        output.push_str(&rest[..i]);
+        let after = &rest[i + 2..];
+        let param = after.find("}}").and_then(|j| {
+            let name = after[..j].trim();
+            Some((params.iter().position(|param| param == name)?, j))
+        });
+        match param {
+            Some((k, j)) if !escaped => {
+                if output.len() + args[k].len() > limit {
+                    return None;
+                }
+                output.push_str(&args[k]);
+                rest = &after[j + 2..];
+            }
+            _ => {
+                output.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    Some(output).filter(|output| output.len() <= limit)
+}
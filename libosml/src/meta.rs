@@ -0,0 +1,92 @@
+use super::*;
+
+//  `[meta title="Hello World" description="..." date=2023-01-31 tags=rust,web]`
+//  Values are either quoted, where `\"` and `\\` can be escaped, or run until the next whitespace.
+//  Expects the character after the name.
+pub fn parse_meta(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+    start: Position,
+) -> Result<(Line, Pos)> {
+    loop {
+        let Some(vline) = p.lines.get(line) else {
+            p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+            return Ok((line, 0));
+        };
+        let Some(&c) = vline.get(pos) else {
+            line += 1;
+            pos = 0;
+            continue;
+        };
+        if is_whitespace(c) {
+            pos += 1;
+            continue;
+        }
+        if c == ']' {
+            return Ok((line, pos + 1));
+        }
+        if c == '%' && matches!(vline.get(pos + 1), Some('%' | '{')) {
+            (line, pos) = skip_comment(p, line, pos)?.unwrap();
+            continue;
+        }
+
+        let key_start = Position::new(line, pos);
+        let mut key = String::new();
+        while let Some(&c) = vline.get(pos).filter(|&&c| is_valid_ch(c)) {
+            key.push(c);
+            pos += 1;
+        }
+        if key.is_empty() || vline.get(pos) != Some(&'=') {
+            //  Skip to whatever comes next.
+            while vline
+                .get(pos)
+                .is_some_and(|&c| !is_whitespace(c) && c != ']')
+            {
+                pos += 1;
+            }
+            p.recover(Error::span(
+                Span::new(key_start, Position::new(line, pos)),
+                ErrorType::BadMetadata,
+            ))?;
+            continue;
+        }
+        pos += 1;
+
+        let value_start = Position::new(line, pos);
+        let value;
+        (value, pos) = parse_value(p, line, pos, ErrorType::BadMetadata)?;
+        let value_span = Span::new(value_start, Position::new(line, pos));
+
+        let meta = &mut p.meta;
+        match key.as_str() {
+            "title" => meta.title = Some(value),
+            "description" => meta.description = Some(value),
+            "date" if is_date(&value) => meta.date = Some(value),
+            "date" => p.recover(Error::span(value_span, ErrorType::BadMetadataDate))?,
+            "tags" => {
+                meta.tags = value
+                    .split(',')
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect()
+            }
+            _ => p.recover(Error::span(
+                Span::new(
+                    key_start,
+                    Position::new(key_start.line, key_start.pos + key.len()),
+                ),
+                ErrorType::BadMetadata,
+            ))?,
+        }
+    }
+}
+
+//  YYYY-MM-DD, so that dates sort properly as strings.
+fn is_date(s: &str) -> bool {
+    s.len() == 10
+        && s.char_indices().all(|(idx, c)| match idx {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
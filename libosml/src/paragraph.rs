@@ -0,0 +1,92 @@
+use super::{Inline, Node, Paragraph, Span};
+
+//  Groups runs of text, links, images and footnote references into paragraphs, split wherever there's an empty line.
+//  Lists, code, tables, nested blocks and raw output stay in between paragraphs rather than in them.
+//  A block that is nothing but a single run of text, like `[title Hello]`, is left as it is.
+pub fn group_paragraphs(children: Vec<Node>) -> Vec<Node> {
+    if children.iter().all(is_inline) {
+        return children;
+    }
+    let mut output = Vec::new();
+    let mut paragraph = Vec::new();
+    for node in children {
+        if is_inline(&node) {
+            paragraph.push(node);
+            continue;
+        }
+        push_paragraph(&mut paragraph, &mut output);
+        if !matches!(node, Node::Break(_)) {
+            output.push(node);
+        }
+    }
+    push_paragraph(&mut paragraph, &mut output);
+    output
+}
+
+fn is_inline(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Inline(_) | Node::Link(_) | Node::Image(_) | Node::FootnoteRef(_)
+    )
+}
+
+fn push_paragraph(paragraph: &mut Vec<Node>, output: &mut Vec<Node>) {
+    let mut children = std::mem::take(paragraph);
+    trim_inlines(&mut children);
+    let (Some(first), Some(last)) = (
+        children.first().and_then(Node::span),
+        children.last().and_then(Node::span),
+    ) else {
+        return;
+    };
+    output.push(Node::Paragraph(Paragraph {
+        children,
+        span: Span::new(first.start, last.end),
+    }));
+}
+
+//  Drops the whitespace that joining lines leaves at either end of a run of text.
+pub(crate) fn trim_inlines(nodes: &mut Vec<Node>) {
+    while let Some(Node::Inline(inline)) = nodes.last_mut() {
+        if !trim_inline(inline, false) {
+            break;
+        }
+        nodes.pop();
+    }
+    while let Some(Node::Inline(inline)) = nodes.first_mut() {
+        if !trim_inline(inline, true) {
+            break;
+        }
+        nodes.remove(0);
+    }
+}
+
+//  Returns whether nothing is left.
+fn trim_inline(inline: &mut Inline, start: bool) -> bool {
+    match inline {
+        Inline::Text { text, .. } => {
+            *text = match start {
+                true => text.trim_start(),
+                false => text.trim_end(),
+            }
+            .to_string();
+            text.is_empty()
+        }
+        Inline::Format { children, .. } => {
+            loop {
+                let child = match start {
+                    true => children.first_mut(),
+                    false => children.last_mut(),
+                };
+                if !child.is_some_and(|child| trim_inline(child, start)) {
+                    break;
+                }
+                match start {
+                    true => children.remove(0),
+                    false => children.pop().unwrap(),
+                };
+            }
+            children.is_empty()
+        }
+    }
+}
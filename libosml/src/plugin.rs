@@ -0,0 +1,19 @@
+use super::*;
+
+//  Takes over blocks by name, see `Context::plugins`.
+//  The block's body is parsed as usual before `render` is called, so plugins only deal with the tree.
+//  Hooks take `&mut self`, so plugins are free to keep state between blocks.
+pub trait Plugin {
+    //  Called when `[name` is found, before the body is parsed.
+    fn open(&mut self, _name: &str, _start: Position) -> Result<()> {
+        Ok(())
+    }
+
+    //  Turns the parsed block into whatever should take its place in the document.
+    fn render(&mut self, block: Block) -> Result<Vec<Node>>;
+
+    //  Called once the whole document has been parsed.
+    fn finish(&mut self, _document: &mut Document) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use super::*;
+
+mod html;
+mod markdown;
+mod text;
+
+pub(crate) use html::escape;
+pub use html::HtmlRenderer;
+pub use markdown::MarkdownRenderer;
+pub use text::TextRenderer;
+
+//  Turns a parsed `Document` into an output format.
+pub trait Renderer {
+    fn render(&self, document: &Document) -> String;
+}
+
+//  Ends the current output line, dropping any trailing spaces left behind by line joins.
+fn end_line(output: &mut String) {
+    output.truncate(output.trim_end_matches(' ').len());
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+}
+
+//  Ends the current output line and leaves an empty one after it.
+fn end_paragraph(output: &mut String) {
+    end_line(output);
+    if !output.is_empty() && !output.ends_with("\n\n") {
+        output.push('\n');
+    }
+}
+
+//  Leaves exactly one new line at the end of the output.
+fn end_document(output: &mut String) {
+    output.truncate(output.trim_end().len());
+    if !output.is_empty() {
+        output.push('\n');
+    }
+}
+
+//  `[x] ` or `[ ] ` in front of task items.
+fn render_checkbox(checked: Option<bool>, output: &mut String) {
+    match checked {
+        Some(true) => output.push_str("[x] "),
+        Some(false) => output.push_str("[ ] "),
+        None => {}
+    }
+}
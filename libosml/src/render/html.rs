@@ -0,0 +1,274 @@
+use super::*;
+
+//  The default, `<div class='name'>` for blocks, or whichever element `Context::elements` picked, and `<b>`, `<ul>`, etc. for everything else.
+//  All text is escaped, `Node::Raw` is the only way to get markup into the output.
+//  Only the document's content is rendered, see `Template` for the rest of the page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, document: &Document) -> String {
+        let mut output = String::new();
+        render_nodes(&document.children, &mut output);
+        render_footnotes(&document.footnotes, &mut output);
+        output
+    }
+}
+
+fn render_nodes(nodes: &[Node], output: &mut String) {
+    nodes.iter().for_each(|node| render_node(node, output));
+}
+
+fn render_node(node: &Node, output: &mut String) {
+    match node {
+        Node::Block(block) => {
+            //  Elements can't be escaped, so anything that isn't a plain tag name is ignored.
+            let element = block
+                .element
+                .as_deref()
+                .filter(|element| is_tag_name(element))
+                .unwrap_or("div");
+            output.push_str(&format!("<{}", element));
+            render_attributes(Some(&block.name), &block.attributes, output);
+            output.push('>');
+            render_nodes(&block.children, output);
+            output.push_str(&format!("</{}>", element));
+        }
+        Node::Inline(inline) => render_inline(inline, output),
+        Node::List(list) => render_list(list, output),
+        Node::FootnoteRef(footnote) => match footnote.number {
+            Some(number) => output.push_str(&format!(
+                "<sup class='footnote-ref'><a href='#fn-{}' id='{}'>{}</a></sup>",
+                number,
+                footnote_ref_id(number, footnote.index),
+                number
+            )),
+            None => output.push_str("<sup class='footnote-ref'>?</sup>"),
+        },
+        Node::DefinitionList(list) => {
+            output.push_str("<dl>");
+            for definition in list.entries.iter() {
+                output.push_str("<dt>");
+                render_nodes(&definition.term, output);
+                output.push_str("</dt><dd>");
+                render_nodes(&definition.children, output);
+                output.push_str("</dd>");
+            }
+            output.push_str("</dl>");
+        }
+        Node::Code(code) => render_code(code, output),
+        Node::Table(table) => render_table(table, output),
+        Node::Link(link) => {
+            output.push_str("<a href='");
+            escape(&link.href, output);
+            output.push('\'');
+            render_attributes(None, &link.attributes, output);
+            output.push('>');
+            if link.children.is_empty() {
+                escape(&link.href, output);
+            }
+            render_nodes(&link.children, output);
+            output.push_str("</a>");
+        }
+        Node::Image(image) => {
+            output.push_str("<img src='");
+            escape(&image.src, output);
+            output.push('\'');
+            if let Some(alt) = &image.alt {
+                output.push_str(" alt='");
+                escape(alt, output);
+                output.push('\'');
+            }
+            render_attributes(None, &image.attributes, output);
+            output.push('>');
+        }
+        Node::Paragraph(paragraph) => {
+            output.push_str("<p>");
+            render_nodes(&paragraph.children, output);
+            output.push_str("</p>");
+        }
+        Node::Break(_) => output.push_str("<br><br>"),
+        Node::Raw(raw) => output.push_str(raw),
+    }
+}
+
+fn render_inline(inline: &Inline, output: &mut String) {
+    match inline {
+        Inline::Text { text, .. } => escape(text, output),
+        Inline::Format {
+            format, children, ..
+        } => {
+            let tag = format_tag(*format);
+            output.push_str(&format!("<{}>", tag));
+            children.iter().for_each(|c| render_inline(c, output));
+            output.push_str(&format!("</{}>", tag));
+        }
+    }
+}
+
+//  Known languages are split into `<span class='hl-...'>`, see `TokenKind::class`.
+fn render_code(code: &Code, output: &mut String) {
+    output.push_str("<pre");
+    render_attributes(None, &code.attributes, output);
+    output.push('>');
+    let Some(lang) = &code.lang else {
+        output.push_str("<code>");
+        escape(&code.text, output);
+        output.push_str("</code></pre>");
+        return;
+    };
+    output.push_str("<code class='language-");
+    escape(lang, output);
+    output.push_str("'>");
+    match highlight(lang, &code.text) {
+        Some(tokens) => {
+            for token in tokens {
+                match token.kind {
+                    Some(kind) => {
+                        output.push_str(&format!("<span class='{}'>", kind.class()));
+                        escape(&token.text, output);
+                        output.push_str("</span>");
+                    }
+                    None => escape(&token.text, output),
+                }
+            }
+        }
+        None => escape(&code.text, output),
+    }
+    output.push_str("</code></pre>");
+}
+
+fn render_table(table: &Table, output: &mut String) {
+    output.push_str("<table");
+    render_attributes(None, &table.attributes, output);
+    output.push('>');
+    if !table.head.is_empty() {
+        output.push_str("<thead>");
+        render_rows(&table.head, &table.align, "th", output);
+        output.push_str("</thead>");
+    }
+    output.push_str("<tbody>");
+    render_rows(&table.body, &table.align, "td", output);
+    output.push_str("</tbody></table>");
+}
+
+fn render_rows(rows: &[Vec<TableCell>], align: &[Align], tag: &str, output: &mut String) {
+    for row in rows {
+        output.push_str("<tr>");
+        for (i, cell) in row.iter().enumerate() {
+            match align.get(i) {
+                Some(Align::Left) => {
+                    output.push_str(&format!("<{} style='text-align: left'>", tag))
+                }
+                Some(Align::Center) => {
+                    output.push_str(&format!("<{} style='text-align: center'>", tag))
+                }
+                Some(Align::Right) => {
+                    output.push_str(&format!("<{} style='text-align: right'>", tag))
+                }
+                _ => output.push_str(&format!("<{}>", tag)),
+            }
+            render_nodes(&cell.children, output);
+            output.push_str(&format!("</{}>", tag));
+        }
+        output.push_str("</tr>");
+    }
+}
+
+//  The first reference to a note is `fnref-1`, later ones are `fnref-1-2` and so on.
+fn footnote_ref_id(number: usize, index: usize) -> String {
+    match index {
+        1 => format!("fnref-{}", number),
+        _ => format!("fnref-{}-{}", number, index),
+    }
+}
+
+fn render_footnotes(footnotes: &[Footnote], output: &mut String) {
+    if footnotes.is_empty() {
+        return;
+    }
+    output.push_str("<section class='footnotes'><ol>");
+    for footnote in footnotes.iter() {
+        output.push_str(&format!("<li id='fn-{}'>", footnote.number));
+        render_nodes(&footnote.children, output);
+        for index in 1..=footnote.references {
+            output.push_str(&format!(
+                " <a href='#{}' class='footnote-back'>↩</a>",
+                footnote_ref_id(footnote.number, index)
+            ));
+        }
+        output.push_str("</li>");
+    }
+    output.push_str("</ol></section>");
+}
+
+fn render_list(list: &List, output: &mut String) {
+    output.push_str(if list.ordered { "<ol>" } else { "<ul>" });
+    for item in list.items.iter() {
+        match item.checked {
+            Some(checked) => {
+                output.push_str("<li class='task'><input type='checkbox' disabled");
+                if checked {
+                    output.push_str(" checked");
+                }
+                output.push_str("> ");
+            }
+            None => output.push_str("<li>"),
+        }
+        render_nodes(&item.children, output);
+        output.push_str("</li>");
+    }
+    output.push_str(if list.ordered { "</ol>" } else { "</ul>" });
+}
+
+const fn format_tag(format: Format) -> &'static str {
+    match format {
+        Format::Bold => "b",
+        Format::Italic => "i",
+        Format::Underline => "u",
+        Format::Strikethrough => "s",
+    }
+}
+
+//  `class` comes first, starting with `class` given here, usually the block's name.
+fn render_attributes(class: Option<&str>, attributes: &Attributes, output: &mut String) {
+    let extra = attributes
+        .iter()
+        .find(|(key, _)| key == "class")
+        .map(|(_, value)| value.as_str());
+    let class = match (class, extra) {
+        (Some(class), Some(extra)) => Some(format!("{} {}", class, extra)),
+        (class, extra) => class.or(extra).map(str::to_string),
+    };
+    if let Some(class) = class {
+        output.push_str(" class='");
+        escape(&class, output);
+        output.push('\'');
+    }
+    for (key, value) in attributes.iter().filter(|(key, _)| key != "class") {
+        output.push(' ');
+        escape(key, output);
+        output.push_str("='");
+        escape(value, output);
+        output.push('\'');
+    }
+}
+
+fn is_tag_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+//  Safe for both text and quoted attribute values.
+pub(crate) fn escape(s: &str, output: &mut String) {
+    for c in s.chars() {
+        match c {
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '&' => output.push_str("&amp;"),
+            '\'' => output.push_str("&#39;"),
+            '"' => output.push_str("&quot;"),
+            _ => output.push(c),
+        }
+    }
+}
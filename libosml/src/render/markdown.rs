@@ -0,0 +1,249 @@
+use super::*;
+
+//  CommonMark, blocks become paragraphs since Markdown has no notion of named blocks.
+//  Blocks mapped to headings or quotes by `Context::elements` are the exception.
+//  Underline has no Markdown equivalent, so it is written as inline HTML, as is raw output from plugins.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn render(&self, document: &Document) -> String {
+        let mut output = String::new();
+        render_nodes(&document.children, &mut output);
+        if !document.footnotes.is_empty() {
+            end_paragraph(&mut output);
+        }
+        for footnote in document.footnotes.iter() {
+            output.push_str(&format!("[^{}]: ", footnote.number));
+            render_nodes(&footnote.children, &mut output);
+            end_line(&mut output);
+        }
+        end_document(&mut output);
+        output
+    }
+}
+
+fn render_nodes(nodes: &[Node], output: &mut String) {
+    nodes.iter().for_each(|node| render_node(node, output));
+}
+
+fn render_node(node: &Node, output: &mut String) {
+    match node {
+        Node::Block(block) => {
+            end_paragraph(output);
+            match block.element.as_deref() {
+                Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                    let level = block.element.as_ref().unwrap()[1..].parse().unwrap();
+                    output.push_str(&"#".repeat(level));
+                    output.push(' ');
+                    render_nodes(&block.children, output);
+                }
+                Some("blockquote") => {
+                    let mut quote = String::new();
+                    render_nodes(&block.children, &mut quote);
+                    end_document(&mut quote);
+                    for line in quote.lines() {
+                        output.push_str(&format!("> {}\n", line).replace("> \n", ">\n"));
+                    }
+                }
+                _ => render_nodes(&block.children, output),
+            }
+            end_paragraph(output);
+        }
+        Node::Inline(inline) => render_inline(inline, output),
+        Node::FootnoteRef(footnote) => match footnote.number {
+            Some(number) => output.push_str(&format!("[^{}]", number)),
+            None => output.push_str("\\[^?\\]"),
+        },
+        Node::List(list) => {
+            end_paragraph(output);
+            render_list(list, output);
+            end_paragraph(output);
+        }
+        //  As in Pandoc and PHP Markdown Extra, CommonMark has no definition lists.
+        Node::DefinitionList(list) => {
+            end_paragraph(output);
+            for definition in list.entries.iter() {
+                render_nodes(&definition.term, output);
+                end_line(output);
+                output.push_str(": ");
+                render_nodes(&definition.children, output);
+                end_paragraph(output);
+            }
+        }
+        Node::Code(code) => {
+            //  The fence has to be longer than any run of backticks in the code.
+            let longest = code
+                .text
+                .split(|c| c != '`')
+                .map(str::len)
+                .max()
+                .unwrap_or(0);
+            let fence = "`".repeat(longest.max(2) + 1);
+            end_paragraph(output);
+            let lang = code.lang.as_deref().unwrap_or_default();
+            output.push_str(&format!("{}{}\n{}\n{}", fence, lang, code.text, fence));
+            end_paragraph(output);
+        }
+        Node::Link(link) => {
+            output.push('[');
+            if link.children.is_empty() {
+                render_inline_text(&link.href, output);
+            }
+            render_nodes(&link.children, output);
+            output.push_str("](");
+            render_destination(&link.href, output);
+            output.push(')');
+        }
+        Node::Image(image) => {
+            output.push_str("![");
+            render_inline_text(image.alt.as_deref().unwrap_or_default(), output);
+            output.push_str("](");
+            render_destination(&image.src, output);
+            output.push(')');
+        }
+        Node::Table(table) => {
+            end_paragraph(output);
+            render_table(table, output);
+            end_paragraph(output);
+        }
+        Node::Paragraph(paragraph) => {
+            end_paragraph(output);
+            render_nodes(&paragraph.children, output);
+            end_paragraph(output);
+        }
+        Node::Break(_) => end_paragraph(output),
+        Node::Raw(raw) => output.push_str(raw),
+    }
+}
+
+fn render_inline(inline: &Inline, output: &mut String) {
+    match inline {
+        Inline::Text { text, .. } => render_inline_text(text, output),
+        Inline::Format {
+            format, children, ..
+        } => {
+            let (open, close) = match format {
+                Format::Bold => ("**", "**"),
+                Format::Italic => ("*", "*"),
+                Format::Underline => ("<u>", "</u>"),
+                Format::Strikethrough => ("~~", "~~"),
+            };
+            output.push_str(open);
+            children.iter().for_each(|c| render_inline(c, output));
+            output.push_str(close);
+        }
+    }
+}
+
+//  Text that would start a list or quote where a line or list item starts gets its marker escaped,
+//  as in `\- not a list`, `1\. not a list` or `\> not a quote`.
+fn render_inline_text(text: &str, output: &mut String) {
+    let mut block_start = at_block_start(output);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if block_start && c.is_ascii_digit() {
+            output.push(c);
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                output.push(digit);
+            }
+            if chars.peek().is_some_and(|&c| c == '.' || c == ')') {
+                output.push('\\');
+            }
+            block_start = false;
+            continue;
+        }
+        if ['\\', '*', '_', '~', '`', '[', ']', '#', '<', '&', '|'].contains(&c)
+            || (block_start && ['-', '+', '>'].contains(&c))
+        {
+            output.push('\\');
+        }
+        output.push(c);
+        block_start = c == '\n' || (block_start && c == ' ');
+    }
+}
+
+//  Whether the output is at the start of a line, or right after the marker of a list item.
+fn at_block_start(output: &str) -> bool {
+    let line = output[output.rfind('\n').map_or(0, |i| i + 1)..].trim_start();
+    let is_marker = |marker: &str| {
+        marker == "-"
+            || marker
+                .strip_suffix('.')
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    };
+    line.is_empty() || line.strip_suffix(' ').is_some_and(is_marker)
+}
+
+//  Wrapped in `<...>` so that spaces and parentheses can't end the address early.
+fn render_destination(href: &str, output: &mut String) {
+    output.push('<');
+    for c in href.chars() {
+        if ['<', '>', '\\'].contains(&c) {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+    output.push('>');
+}
+
+//  Markdown tables always have a head, so an empty one is made up when there's none.
+fn render_table(table: &Table, output: &mut String) {
+    let columns = table
+        .head
+        .iter()
+        .chain(table.body.iter())
+        .map(Vec::len)
+        .chain([table.align.len(), 1])
+        .max()
+        .unwrap();
+    let empty = Vec::new();
+    render_row(table.head.first().unwrap_or(&empty), columns, output);
+    for i in 0..columns {
+        output.push_str(match table.align.get(i) {
+            Some(Align::Left) => "| :-- ",
+            Some(Align::Center) => "| :-: ",
+            Some(Align::Right) => "| --: ",
+            _ => "| --- ",
+        });
+    }
+    output.push_str("|\n");
+    for row in table.head.iter().skip(1).chain(table.body.iter()) {
+        render_row(row, columns, output);
+    }
+}
+
+fn render_row(row: &[TableCell], columns: usize, output: &mut String) {
+    for i in 0..columns {
+        output.push_str("| ");
+        if let Some(cell) = row.get(i) {
+            render_nodes(&cell.children, output);
+        }
+        output.push(' ');
+    }
+    output.push_str("|\n");
+}
+
+fn render_list(list: &List, output: &mut String) {
+    //  Four spaces is enough to nest under both `- ` and `1. `.
+    let indent = "    ".repeat(list.depth - 1);
+    for (number, item) in list.items.iter().enumerate() {
+        output.push_str(&indent);
+        if list.ordered {
+            output.push_str(&format!("{}. ", number + 1));
+        } else {
+            output.push_str("- ");
+        }
+        render_checkbox(item.checked, output);
+        for node in item.children.iter() {
+            //  Nested lists go on the next line, a blank one would loosen the list.
+            if let Node::List(nested) = node {
+                end_line(output);
+                render_list(nested, output);
+            } else {
+                render_node(node, output);
+            }
+        }
+        end_line(output);
+    }
+}
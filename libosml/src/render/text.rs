@@ -0,0 +1,124 @@
+use super::*;
+
+//  Plain text, formatting is dropped and blocks are separated by new lines.
+//  Raw output from plugins is skipped since it is meant for another format.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn render(&self, document: &Document) -> String {
+        let mut output = String::new();
+        render_nodes(&document.children, &mut output);
+        if !document.footnotes.is_empty() {
+            end_paragraph(&mut output);
+        }
+        for footnote in document.footnotes.iter() {
+            output.push_str(&format!("[{}] ", footnote.number));
+            render_nodes(&footnote.children, &mut output);
+            end_line(&mut output);
+        }
+        end_document(&mut output);
+        output
+    }
+}
+
+fn render_nodes(nodes: &[Node], output: &mut String) {
+    for (i, node) in nodes.iter().enumerate() {
+        //  Paragraphs are set apart from each other, but not from the lists and blocks around them.
+        if i > 0
+            && matches!(
+                (&nodes[i - 1], node),
+                (Node::Paragraph(_), Node::Paragraph(_))
+            )
+        {
+            end_paragraph(output);
+        }
+        render_node(node, output);
+    }
+}
+
+fn render_node(node: &Node, output: &mut String) {
+    match node {
+        Node::Block(block) => {
+            end_line(output);
+            render_nodes(&block.children, output);
+            end_line(output);
+        }
+        Node::Inline(inline) => render_inline(inline, output),
+        Node::FootnoteRef(footnote) => match footnote.number {
+            Some(number) => output.push_str(&format!("[{}]", number)),
+            None => output.push_str("[?]"),
+        },
+        Node::List(list) => {
+            end_line(output);
+            render_list(list, output);
+        }
+        Node::DefinitionList(list) => {
+            end_line(output);
+            for definition in list.entries.iter() {
+                render_nodes(&definition.term, output);
+                end_line(output);
+                output.push_str("  ");
+                render_nodes(&definition.children, output);
+                end_line(output);
+            }
+        }
+        Node::Code(code) => {
+            end_line(output);
+            output.push_str(&code.text);
+            end_line(output);
+        }
+        Node::Link(link) => {
+            render_nodes(&link.children, output);
+            if link.children.is_empty() {
+                output.push_str(&link.href);
+            } else {
+                output.push_str(&format!(" ({})", link.href));
+            }
+        }
+        Node::Image(image) => output.push_str(image.alt.as_deref().unwrap_or_default()),
+        Node::Table(table) => {
+            end_line(output);
+            for row in table.head.iter().chain(table.body.iter()) {
+                for (i, cell) in row.iter().enumerate() {
+                    if i > 0 {
+                        output.push_str(" | ");
+                    }
+                    render_nodes(&cell.children, output);
+                }
+                end_line(output);
+            }
+        }
+        Node::Paragraph(paragraph) => {
+            end_line(output);
+            render_nodes(&paragraph.children, output);
+            end_line(output);
+        }
+        Node::Break(_) => end_paragraph(output),
+        Node::Raw(_) => {}
+    }
+}
+
+fn render_inline(inline: &Inline, output: &mut String) {
+    match inline {
+        Inline::Text { text, .. } => output.push_str(text),
+        Inline::Format { children, .. } => {
+            children.iter().for_each(|c| render_inline(c, output));
+        }
+    }
+}
+
+fn render_list(list: &List, output: &mut String) {
+    let indent = "  ".repeat(list.depth - 1);
+    for (number, item) in list.items.iter().enumerate() {
+        output.push_str(&indent);
+        if list.ordered {
+            output.push_str(&format!("{}. ", number + 1));
+        } else {
+            output.push_str("- ");
+        }
+        render_checkbox(item.checked, output);
+        render_nodes(&item.children, output);
+        end_line(output);
+    }
+}
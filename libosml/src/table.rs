@@ -0,0 +1,153 @@
+use super::{
+    is_whitespace, parse_text_line, skip_comment, trim_inlines, Align, Attributes, Error,
+    ErrorType, InlineBuilder, Line, Node, Parser, Pos, Position, Result, Span, Table, TableCell,
+    TextMode,
+};
+
+//  [table
+//  | Name  | Price |
+//  |:------|------:|
+//  | *Tea* | 3     |
+//  ]
+//  Rows above the separator make up the head, colons in the separator align their column.
+//  A first row like `| - | - |` has nothing above it, so it is a plain row rather than a separator.
+//  Expects the character after the name.
+pub fn parse_table(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+    start: Position,
+    attributes: Attributes,
+    output: &mut Vec<Node>,
+) -> Result<(Line, Pos)> {
+    let mut head = Vec::new();
+    let mut body = Vec::new();
+    let mut align = Vec::new();
+    loop {
+        let Some(vline) = p.lines.get(line) else {
+            p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+            pos = 0;
+            break;
+        };
+        match vline.get(pos) {
+            None => {
+                line += 1;
+                pos = 0;
+            }
+            Some(&c) if is_whitespace(c) => pos += 1,
+            Some(']') => {
+                pos += 1;
+                break;
+            }
+            Some('%') if matches!(vline.get(pos + 1), Some('%' | '{')) => {
+                (line, pos) = skip_comment(p, line, pos)?.unwrap();
+            }
+            Some('|') if head.is_empty() && !body.is_empty() && is_separator(&vline[pos..]) => {
+                align = parse_align(&vline[pos..]);
+                head = std::mem::take(&mut body);
+                line += 1;
+                pos = 0;
+            }
+            Some('|') => {
+                let (row, ended);
+                (row, line, pos, ended) = parse_row(p, line, pos + 1, start)?;
+                body.push(row);
+                if ended {
+                    break;
+                }
+            }
+            Some(_) => {
+                let span = Span::new(Position::new(line, pos), Position::new(line, vline.len()));
+                p.recover(Error::span(span, ErrorType::BadTableRow))?;
+                line += 1;
+                pos = 0;
+            }
+        }
+    }
+    output.push(Node::Table(Table {
+        attributes,
+        head,
+        body,
+        align,
+        span: Span::new(start, Position::new(line, pos)),
+    }));
+    Ok((line, pos))
+}
+
+//  Expects the character after the row's opening '|'.
+//  Also returns whether the table ended along with the row.
+fn parse_row(
+    p: &mut Parser,
+    mut line: Line,
+    mut pos: Pos,
+    start: Position,
+) -> Result<(Vec<TableCell>, Line, Pos, bool)> {
+    let mut cells = Vec::new();
+    loop {
+        let cell_start = Position::new(line, pos);
+        let mut children = Vec::new();
+        let (done, nline, npos) = parse_text_line(
+            p,
+            line,
+            pos,
+            &mut children,
+            &mut InlineBuilder::default(),
+            TextMode::Cell,
+            start,
+        )?;
+        trim_inlines(&mut children);
+        let ended_by = match done {
+            true => npos
+                .checked_sub(1)
+                .and_then(|end| p.lines.get(nline)?.get(end).copied()),
+            false => None,
+        };
+        let cell_end = match ended_by {
+            Some(_) => Position::new(nline, npos - 1),
+            None => Position::new(nline.saturating_sub(1), p.lines[line].len()),
+        };
+        (line, pos) = (nline, npos);
+        //  The space after a trailing '|' isn't a cell.
+        if ended_by == Some('|') || !children.is_empty() {
+            cells.push(TableCell {
+                children,
+                span: Span::new(cell_start, cell_end),
+            });
+        }
+
+        if ended_by != Some('|') {
+            return Ok((cells, line, pos, done));
+        }
+        let rest = &p.lines[line][pos..];
+        match rest.iter().find(|&&c| !is_whitespace(c)) {
+            None => return Ok((cells, line + 1, 0, false)),
+            Some(']') => return Ok((cells, line, pos, false)),
+            Some(_) => {}
+        }
+    }
+}
+
+//  `|:---|:-:|---:|`
+fn is_separator(rest: &[char]) -> bool {
+    rest.contains(&'-')
+        && rest
+            .iter()
+            .all(|&c| matches!(c, '|' | '-' | ':') || is_whitespace(c))
+}
+
+fn parse_align(rest: &[char]) -> Vec<Align> {
+    let rest: String = rest.iter().collect();
+    rest.trim()
+        .trim_matches('|')
+        .split('|')
+        .map(|column| {
+            let column = column.trim();
+            match (column.starts_with(':'), column.ends_with(':')) {
+                (true, true) => Align::Center,
+                (true, false) => Align::Left,
+                (false, true) => Align::Right,
+                (false, false) => Align::None,
+            }
+        })
+        .collect()
+}
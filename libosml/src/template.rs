@@ -0,0 +1,86 @@
+use super::*;
+
+pub const CONTENT_PLACEHOLDER: &str = "{{content}}";
+
+//  <Boring HTML Stuff>
+const DEFAULT_TEMPLATE: &str = "<html><head>{{head}}</head><body>{{content}}</body></html>";
+
+//  A page layout that the rendered document is placed into, see `CONTENT_PLACEHOLDER`.
+//  The document's metadata fills in the following placeholders, escaped for HTML:
+//      {{head}}         <title> and <meta> tags for whatever metadata is set.
+//      {{title}}        Each of these is empty when unset.
+//      {{description}}
+//      {{date}}
+//      {{tags}}         Comma separated.
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+}
+
+impl Template {
+    pub fn new(source: String) -> Result<Self> {
+        if !source.contains(CONTENT_PLACEHOLDER) {
+            Err(Error::null(ErrorType::NoTemplateContent))?
+        }
+        Ok(Template { source })
+    }
+
+    pub fn apply(&self, content: &str, meta: &Metadata) -> String {
+        let escaped = |value: &str| {
+            let mut output = String::new();
+            escape(value, &mut output);
+            output
+        };
+        let title = meta.title.as_deref().map(escaped);
+        let description = meta.description.as_deref().map(escaped);
+        let date = meta.date.as_deref().map(escaped);
+        let tags = escaped(&meta.tags.join(", "));
+
+        let mut head = String::new();
+        if let Some(title) = &title {
+            head.push_str(&format!("<title>{}</title>", title));
+        }
+        if let Some(description) = &description {
+            head.push_str(&format!(
+                "<meta name='description' content='{}'>",
+                description
+            ));
+        }
+        if !meta.tags.is_empty() {
+            head.push_str(&format!("<meta name='keywords' content='{}'>", tags));
+        }
+
+        //  Done in a single pass, so that placeholders coming from the document are left alone.
+        let mut output = String::new();
+        let mut rest = self.source.as_str();
+        while let Some(idx) = rest.find("{{") {
+            output.push_str(&rest[..idx]);
+            rest = &rest[idx..];
+            let len = rest.find("}}").map(|end| end + 2).unwrap_or(2);
+            match &rest[..len] {
+                CONTENT_PLACEHOLDER => output.push_str(content),
+                "{{head}}" => output.push_str(&head),
+                "{{title}}" => output.push_str(title.as_deref().unwrap_or("")),
+                "{{description}}" => output.push_str(description.as_deref().unwrap_or("")),
+                "{{date}}" => output.push_str(date.as_deref().unwrap_or("")),
+                "{{tags}}" => output.push_str(&tags),
+                _ => {
+                    output.push_str("{{");
+                    rest = &rest[2..];
+                    continue;
+                }
+            }
+            rest = &rest[len..];
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template {
+            source: DEFAULT_TEMPLATE.to_string(),
+        }
+    }
+}
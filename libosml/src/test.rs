@@ -50,48 +50,1303 @@ This is just normal text.
             <br><br>\
             <ul>\
                 <li>FirstElement + 10 </li>\
-                <li>Second Element </li>\
-                <ul>\
-                    <li>Nested Element </li>\
-                </ul>\
+                <li>Second Element \
+                    <ul>\
+                        <li>Nested Element </li>\
+                    </ul>\
+                </li>\
             </ul>\
             This is just normal text. <br><br>\
         </div>\
     </body>\
 </html>";
 
-    fn my_plugin(
-        lines: &Vec<Vec<char>>,
-        mut line: Line,
-        mut pos: Pos,
-        mut output: String,
-        ctx: &Context,
-    ) -> Result<(Line, Pos, String)> {
-        output = format!("{}<plugin>", output);
-        let mut last_list_was_ordered = None;
-        let start_line = line;
-        loop {
-            let (done, nline, npos, noutput, nlast_list_was_ordered) =
-                parse_text_line(lines, line, pos, output, ctx, true, start_line, last_list_was_ordered)?;
-
-            line = nline;
-            pos = npos;
-            output = noutput;
-            last_list_was_ordered = nlast_list_was_ordered;
-            if done {
-                break;
-            }
+    struct MyPlugin;
+
+    impl Plugin for MyPlugin {
+        fn render(&mut self, block: Block) -> Result<Vec<Node>> {
+            let mut nodes = vec![Node::Raw("<plugin>".to_string())];
+            nodes.extend(block.children);
+            nodes.push(Node::Raw("</plugin>".to_string()));
+            Ok(nodes)
         }
-        output = format!("{}</plugin>", output);
-        Ok((line, pos, output))
     }
 
     let res = parse(
         my_osml.to_string(),
         Context {
-            plugins: HashMap::from([("plugin".to_string(), my_plugin as ExtCallback)]),
+            plugins: HashMap::from([("plugin".to_string(), Box::new(MyPlugin) as Box<dyn Plugin>)]),
+            legacy_breaks: true,
+            ..Context::create()
         },
     )
     .unwrap();
     assert_eq!(res, expected_result);
 }
+
+#[test]
+fn test_document_tree() {
+    let my_osml = r"
+[outer *bold /both/* text
+    + one
+    ++ two
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(document.children.len(), 1);
+    let Node::Block(outer) = &document.children[0] else {
+        panic!("Expected a block, got {:?}", document.children[0]);
+    };
+    assert_eq!(outer.name, "outer");
+    assert_eq!(
+        outer.span,
+        Span::new(Position::new(1, 0), Position::new(4, 1))
+    );
+
+    let Node::Paragraph(paragraph) = &outer.children[0] else {
+        panic!("Expected a paragraph, got {:?}", outer.children[0]);
+    };
+    let Node::Inline(Inline::Format {
+        format: Format::Bold,
+        children,
+        span,
+    }) = &paragraph.children[0]
+    else {
+        panic!("Expected bold, got {:?}", paragraph.children[0]);
+    };
+    assert_eq!(*span, Span::new(Position::new(1, 7), Position::new(1, 20)));
+    assert!(matches!(
+        &children[1],
+        Inline::Format {
+            format: Format::Italic,
+            ..
+        }
+    ));
+
+    let Node::List(list) = &outer.children[1] else {
+        panic!("Expected a list, got {:?}", outer.children[1]);
+    };
+    assert!(!list.ordered);
+    assert_eq!(list.depth, 1);
+    assert_eq!(list.last_depth(), 2);
+    assert!(matches!(&list.items[0].children[..], [.., Node::List(nested)] if nested.depth == 2));
+}
+
+#[test]
+fn test_renderers() {
+    let my_osml = r"
+[intro Hello *there*, _a\*b_
+    + one
+    ++ two
+    + three
+]
+[outro = first
+= second
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+
+    let text = "\
+Hello there, a*b
+- one
+  - two
+- three
+1. first
+2. second
+";
+    assert_eq!(TextRenderer.render(&document), text);
+
+    let markdown = "\
+Hello **there**, <u>a\\*b</u>
+
+- one
+    - two
+- three
+
+1. first
+2. second
+";
+    assert_eq!(MarkdownRenderer.render(&document), markdown);
+
+    //  Text that looks like the start of a Markdown block stays text.
+    let document = parse_document(
+        "[a - not a list] [b 1. not a list] [c > not a quote] [d\n+ - nor this\n= 2) or this\n] [e 1 - 2 > 0]",
+        &mut Context::create(),
+    )
+    .unwrap();
+    assert_eq!(
+        MarkdownRenderer.render(&document),
+        "\\- not a list\n\n1\\. not a list\n\n\\> not a quote\n\n\
+        - \\- nor this\n\n1. 2\\) or this\n\n1 - 2 > 0\n"
+    );
+}
+
+#[test]
+fn test_error_spans() {
+    let span_of = |osml: &str| match parse_document(osml, &mut Context::create()) {
+        Err(Error {
+            location: Location::Span(span),
+            error,
+            ..
+        }) => (span, error.message()),
+        res => panic!("Expected an error with a span, got {:?}", res),
+    };
+
+    let (span, message) = span_of("[a one *two\nthree]");
+    assert_eq!(span, Span::at(Position::new(0, 7)));
+    assert_eq!(message, ErrorType::UnclosedBold.message());
+
+    let (span, _) = span_of("[a one \\q]");
+    assert_eq!(span, Span::new(Position::new(0, 7), Position::new(0, 9)));
+
+    let (span, _) = span_of("\n  [a-b]");
+    assert_eq!(span, Span::at(Position::new(1, 4)));
+
+    let (span, _) = span_of("[a\n+ one\n+++ three\n]");
+    assert_eq!(span, Span::new(Position::new(2, 0), Position::new(2, 3)));
+
+    let (span, _) = span_of("[a [b one]\n");
+    assert_eq!(span, Span::at(Position::new(0, 0)));
+}
+
+#[test]
+fn test_error_recovery() {
+    let my_osml = r"
+[a *one
+    + two
+    +++ three \q
+]
+stray
+[b _four
+";
+
+    let (document, errors) = parse_document_recover(my_osml, &mut Context::create());
+    let errors: Vec<String> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(
+        errors,
+        [
+            "UnclosedBold",
+            "InvalidListDepth",
+            "StrayBackslash",
+            "ExpectedBlockStart",
+            "UnclosedUnderline",
+            "BlockNoEnd",
+        ]
+    );
+
+    //  Everything that could be parsed is still there.
+    let res = HtmlRenderer.render(&document);
+    assert_eq!(
+        res,
+        "<div class='a'><p><b>one</b></p><ul><li>two <ul><li>three q </li></ul></li></ul></div>\
+        <div class='b'><p><u>four</u></p></div>"
+    );
+
+    assert!(parse_document(my_osml, &mut Context::create()).is_err());
+}
+
+#[test]
+fn test_stateful_plugin() {
+    //  Numbers every note in order of appearance and counts them at the end.
+    struct Notes {
+        count: usize,
+        open: Vec<usize>,
+    }
+
+    impl Plugin for Notes {
+        fn open(&mut self, _name: &str, _start: Position) -> Result<()> {
+            self.count += 1;
+            self.open.push(self.count);
+            Ok(())
+        }
+
+        fn render(&mut self, mut block: Block) -> Result<Vec<Node>> {
+            block.name = format!("note_{}", self.open.pop().unwrap());
+            Ok(vec![Node::Block(block)])
+        }
+
+        fn finish(&mut self, document: &mut Document) -> Result<()> {
+            document
+                .children
+                .push(Node::Raw(format!("<p>{} notes</p>", self.count)));
+            Ok(())
+        }
+    }
+
+    let my_osml = "[note *One*] [text [note Two [note Three]]]";
+    let mut ctx = Context::create();
+    ctx.plugins.insert(
+        "note".to_string(),
+        Box::new(Notes {
+            count: 0,
+            open: Vec::new(),
+        }),
+    );
+    let document = parse_document(my_osml, &mut ctx).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='note_1'><b>One</b></div>\
+        <div class='text'><div class='note_2'><p>Two</p><div class='note_3'>Three</div></div></div>\
+        <p>3 notes</p>"
+    );
+}
+
+#[test]
+fn test_html_escaping() {
+    let my_osml = r#"[a <script>alert('hi') & "bye"<\/script>]"#;
+    let res = parse(my_osml.to_string(), Context::create()).unwrap();
+    assert_eq!(
+        res,
+        "<html><head></head><body><div class='a'>\
+        &lt;script&gt;alert(&#39;hi&#39;) &amp; &quot;bye&quot;&lt;/script&gt;\
+        </div></body></html>"
+    );
+
+    let mut document = parse_document("[a]", &mut Context::create()).unwrap();
+    if let Node::Block(block) = &mut document.children[0] {
+        block.name = "a' onclick='evil()".to_string();
+    }
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a&#39; onclick=&#39;evil()'></div>"
+    );
+}
+
+#[test]
+fn test_template() {
+    let template = "<html><head><title>Hi</title></head><body><nav></nav>{{content}}</body></html>";
+    let mut ctx = Context::create();
+    ctx.template = Some(Template::new(template.to_string()).unwrap());
+    let res = parse("[a Hello]".to_string(), ctx).unwrap();
+    assert_eq!(
+        res,
+        "<html><head><title>Hi</title></head><body><nav></nav>\
+        <div class='a'>Hello</div>\
+        </body></html>"
+    );
+
+    assert!(matches!(
+        Template::new("<html></html>".to_string()),
+        Err(Error {
+            error: ErrorType::NoTemplateContent,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_metadata() {
+    let my_osml = r#"
+[meta title="Hello <World>" description="All about \"OSML\"."
+    date=2023-01-31 tags=rust,web]
+[a Hi]
+"#;
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        document.meta,
+        Metadata {
+            title: Some("Hello <World>".to_string()),
+            description: Some("All about \"OSML\".".to_string()),
+            date: Some("2023-01-31".to_string()),
+            tags: vec!["rust".to_string(), "web".to_string()],
+        }
+    );
+    assert_eq!(document.children.len(), 1);
+
+    let res = parse(my_osml.to_string(), Context::create()).unwrap();
+    assert_eq!(
+        res,
+        "<html><head>\
+        <title>Hello &lt;World&gt;</title>\
+        <meta name='description' content='All about &quot;OSML&quot;.'>\
+        <meta name='keywords' content='rust, web'>\
+        </head><body><div class='a'>Hi</div></body></html>"
+    );
+
+    let (_, errors) = parse_document_recover(
+        "[meta date=tomorrow author=me title]",
+        &mut Context::create(),
+    );
+    let errors: Vec<String> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(errors, ["BadMetadataDate", "BadMetadata", "BadMetadata"]);
+}
+
+#[test]
+fn test_include() {
+    let dir = std::env::temp_dir().join(format!("osml_test_include_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("partials")).unwrap();
+    let write = |name: &str, s: &str| std::fs::write(dir.join(name), s).unwrap();
+    write("page.osml", "[a Top] [b [include partials/nav.osml]]");
+    write("partials/nav.osml", "[nav *Home*]\n[include links.osml]");
+    write("partials/links.osml", "[links One]");
+    write("cycle.osml", "[a] [include partials/cycle.osml]");
+    write("partials/cycle.osml", "[include ../cycle.osml]");
+    write("broken.osml", "[include partials/broken.osml]");
+    write("partials/broken.osml", "[a\n\n[b *bold]");
+
+    let parse_file = |name: &str| {
+        let mut ctx = Context::create();
+        ctx.path = Some(dir.join(name));
+        let source = std::fs::read_to_string(dir.join(name)).unwrap();
+        parse_document_recover(&source, &mut ctx)
+    };
+
+    let (document, errors) = parse_file("page.osml");
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>Top</div>\
+        <div class='b'><nav class='nav'><b>Home</b></nav><div class='links'>One</div></div>"
+    );
+    assert_eq!(
+        document.includes,
+        [
+            dir.join("partials/nav.osml"),
+            dir.join("partials/links.osml")
+        ]
+    );
+
+    let (_, errors) = parse_file("cycle.osml");
+    assert!(matches!(
+        &errors[..],
+        [Error {
+            error: ErrorType::IncludeCycle,
+            file: Some(file),
+            ..
+        }] if *file == dir.join("partials/cycle.osml")
+    ));
+
+    let (_, errors) = parse_file("broken.osml");
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|e| (format!("{:?}", e.error), e.file.clone()))
+        .collect();
+    let broken = Some(dir.join("partials/broken.osml"));
+    assert_eq!(
+        errors,
+        [
+            ("UnclosedBold".to_string(), broken.clone()),
+            ("BlockNoEnd".to_string(), broken),
+        ]
+    );
+
+    let (_, errors) = parse_document_recover("[include missing.osml]", &mut Context::create());
+    assert!(matches!(errors[0].error, ErrorType::IncludeWithoutPath));
+    let mut ctx = Context::create();
+    ctx.path = Some(dir.join("page.osml"));
+    assert!(matches!(
+        parse_document("[include missing.osml]", &mut ctx),
+        Err(Error {
+            error: ErrorType::IncludeNotFound,
+            location: Location::Span(span),
+            ..
+        }) if span == Span::new(Position::new(0, 9), Position::new(0, 21))
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_code_block() {
+    let my_osml = r#"
+[a See: [code a/b*c \n]
+[code
+    if v[0] < 1 {
+        print("_hi_");
+    }
+]]
+[code ```let s = "]";```]
+"#;
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'><p>See:</p><pre><code>a/b*c \\n</code></pre>\
+        <pre><code>    if v[0] &lt; 1 {\n        print(&quot;_hi_&quot;);\n    }</code></pre></div>\
+        <pre><code>let s = &quot;]&quot;;</code></pre>"
+    );
+    assert_eq!(
+        MarkdownRenderer.render(&parse_document("[code a```b]", &mut Context::create()).unwrap()),
+        "````\na```b\n````\n"
+    );
+
+    let (span, message) = match parse_document("[code [a]", &mut Context::create()) {
+        Err(Error {
+            location: Location::Span(span),
+            error,
+            ..
+        }) => (span, error.message()),
+        res => panic!("Expected an error with a span, got {:?}", res),
+    };
+    assert_eq!(span, Span::at(Position::new(0, 0)));
+    assert_eq!(message, ErrorType::BlockNoEnd.message());
+}
+
+#[test]
+fn test_highlight() {
+    let my_osml = r#"[code lang=rust
+let s: Vec<u8> = vec![1, 2]; // "no"
+]"#;
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<pre><code class='language-rust'>\
+        <span class='hl-keyword'>let</span> s: <span class='hl-type'>Vec</span>&lt;\
+        <span class='hl-type'>u8</span>&gt; = <span class='hl-macro'>vec!</span>[\
+        <span class='hl-number'>1</span>, <span class='hl-number'>2</span>]; \
+        <span class='hl-comment'>// &quot;no&quot;</span>\
+        </code></pre>"
+    );
+
+    let classes = |lang: &str, code: &str| {
+        highlight(lang, code)
+            .unwrap()
+            .into_iter()
+            .filter_map(|token| Some((token.kind?.class(), token.text)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        classes("toml", "[package]\nname = 'osml' # hi"),
+        [
+            ("hl-tag", "[package]".to_string()),
+            ("hl-attribute", "name".to_string()),
+            ("hl-string", "'osml'".to_string()),
+            ("hl-comment", "# hi".to_string()),
+        ]
+    );
+    assert_eq!(
+        classes("shell", "echo \"$HOME\" $1#x"),
+        [
+            ("hl-string", "\"$HOME\"".to_string()),
+            ("hl-variable", "$1".to_string()),
+        ]
+    );
+    assert_eq!(
+        classes("html", "<a href='x'>&amp;</a>"),
+        [
+            ("hl-tag", "a".to_string()),
+            ("hl-attribute", "href".to_string()),
+            ("hl-string", "'x'".to_string()),
+            ("hl-escape", "&amp;".to_string()),
+            ("hl-tag", "a".to_string()),
+        ]
+    );
+    assert!(highlight("cobol", "").is_none());
+}
+
+#[test]
+fn test_links_and_images() {
+    let my_osml = r#"[a See [link https://example.com/?a=1&b='2' the *docs*] or [link /home].
+[img cat.png A /tabby/ cat] [img "><script>.png]]"#;
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>See <a href='https://example.com/?a=1&amp;b=&#39;2&#39;'>the <b>docs</b></a> \
+        or <a href='/home'>/home</a>. \
+        <img src='cat.png' alt='A tabby cat'> <img src='&quot;&gt;&lt;script&gt;.png'></div>"
+    );
+    assert_eq!(
+        MarkdownRenderer
+            .render(&parse_document("[link a(b) c] [img x.png]", &mut Context::create()).unwrap()),
+        "[c](<a(b)>)![](<x.png>)\n"
+    );
+
+    let warnings: Vec<_> = document
+        .warnings
+        .iter()
+        .map(|w| (format!("{:?}", w.error), w.location.clone()))
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].0, "MissingAltText");
+    assert!(matches!(warnings[0].1, Location::Span(span) if span.start == Position::new(1, 28)));
+
+    assert!(matches!(
+        parse_document("[link]", &mut Context::create()),
+        Err(Error {
+            error: ErrorType::NoLinkTarget,
+            ..
+        })
+    ));
+
+    //  Script addresses never make it into the page, `data:` only does for images.
+    let (document, errors) = parse_document_recover(
+        "[a [link javascript:alert(document.cookie) hi] [link JavaScript:x] [link data:text/html,x y] \
+        [img data:image/png;base64,AA dot] javascript://x [b {href=vbscript:x} z] \
+        [c {href=\" javascript:alert(1)\"} w]]",
+        &mut Context::create(),
+    );
+    let errors: Vec<_> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(
+        errors,
+        [
+            "UnsafeLinkTarget",
+            "UnsafeLinkTarget",
+            "UnsafeLinkTarget",
+            "BadAttribute",
+            "BadAttribute"
+        ]
+    );
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'><p><a href=''>hi</a> <a href=''></a> <a href=''>y</a> \
+        <img src='data:image/png;base64,AA' alt='dot'> javascript://x</p><div class='b'>z</div><div class='c'>w</div></div>"
+    );
+}
+
+#[test]
+fn test_table() {
+    let my_osml = r"
+[table
+| Name | Price | Note |
+|:-----|------:|:----:|
+| *Tea*   | 3 | a \| b |
+| _Cake_ || [link /cake more] |]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<table><thead><tr>\
+        <th style='text-align: left'>Name</th>\
+        <th style='text-align: right'>Price</th>\
+        <th style='text-align: center'>Note</th>\
+        </tr></thead><tbody><tr>\
+        <td style='text-align: left'><b>Tea</b></td>\
+        <td style='text-align: right'>3</td>\
+        <td style='text-align: center'>a | b</td>\
+        </tr><tr>\
+        <td style='text-align: left'><u>Cake</u></td>\
+        <td style='text-align: right'></td>\
+        <td style='text-align: center'><a href='/cake'>more</a></td>\
+        </tr></tbody></table>"
+    );
+    assert_eq!(
+        MarkdownRenderer.render(&document),
+        "\
+| Name | Price | Note |
+| :-- | --: | :-: |
+| **Tea** | 3 | a \\| b |
+| <u>Cake</u> |  | [more](</cake>) |
+"
+    );
+
+    let (document, errors) =
+        parse_document_recover("[table\n| a | b\nc | d\n| e |\n]", &mut Context::create());
+    let errors: Vec<String> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(errors, ["BadTableRow"]);
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<table><tbody><tr><td>a</td><td>b</td></tr><tr><td>e</td></tr></tbody></table>"
+    );
+
+    //  Without rows above it, a row of dashes is data.
+    let document =
+        parse_document("[table\n| - | - |\n| a | b |\n]", &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<table><tbody><tr><td>-</td><td>-</td></tr><tr><td>a</td><td>b</td></tr></tbody></table>"
+    );
+}
+
+#[test]
+fn test_elements() {
+    let my_osml = "[title Hi] [quote *Wise* words] [card Plain]";
+    let mut ctx = Context::create();
+    ctx.elements
+        .insert("card".to_string(), "article".to_string());
+    ctx.elements
+        .insert("title".to_string(), "h1 onclick=evil()".to_string());
+    let document = parse_document(my_osml, &mut ctx).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='title'>Hi</div>\
+        <blockquote class='quote'><b>Wise</b> words</blockquote>\
+        <article class='card'>Plain</article>"
+    );
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<h1 class='title' id='hi'>Hi</h1>\
+        <blockquote class='quote'><b>Wise</b> words</blockquote>\
+        <div class='card'>Plain</div>"
+    );
+    assert_eq!(
+        MarkdownRenderer.render(&document),
+        "# Hi\n\n> **Wise** words\n\nPlain\n"
+    );
+}
+
+#[test]
+fn test_attributes() {
+    let my_osml = r#"
+[section#intro.wide.dark data-x=1 {title="Say \"hi\"" class=extra}
+    Hello x\=1 [link.button /next Next]]
+[code#ex lang=rust fn main() {}]
+"#;
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    let Node::Block(section) = &document.children[0] else {
+        panic!("Expected a block, got {:?}", document.children[0]);
+    };
+    assert_eq!(section.attribute("id"), Some("intro"));
+    assert_eq!(section.attribute("class"), Some("wide dark extra"));
+    assert_eq!(section.attribute("data-x"), Some("1"));
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<section class='section wide dark extra' id='intro' data-x='1' title='Say &quot;hi&quot;'>\
+        Hello x=1 <a href='/next' class='button'>Next</a></section>\
+        <pre id='ex'><code class='language-rust'><span class='hl-keyword'>fn</span> \
+        <span class='hl-function'>main</span>() {}</code></pre>"
+    );
+
+    let (document, errors) =
+        parse_document_recover("[a# onclick=evil() {x=1 ??} Hi]", &mut Context::create());
+    let errors: Vec<String> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(errors, ["BadAttribute", "BadAttribute", "BadAttribute"]);
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a' x='1'>Hi</div>"
+    );
+
+    //  An unclosed brace runs to the end of the document.
+    let (_, errors) = parse_document_recover("[abc {x=1", &mut Context::create());
+    let errors: Vec<String> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(errors, ["BadAttribute", "BlockNoEnd"]);
+
+    //  Code only takes `#id`, `.class` and `lang=`, the rest of it is code.
+    let document = parse_document(
+        r#"[code lang=json {"a": 1}] [code x=1; y=2]"#,
+        &mut Context::create(),
+    )
+    .unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<pre><code class='language-json'>{<span class='hl-attribute'>&quot;a&quot;</span>: \
+        <span class='hl-number'>1</span>}</code></pre>\
+        <pre><code>x=1; y=2</code></pre>"
+    );
+}
+
+#[test]
+fn test_paragraphs() {
+    let my_osml = r"
+[section
+
+    Hello to everyone who is reading this.
+    This sentence should be on the same line.
+
+    Although, this one will not be.
+    + Lists stay out of paragraphs.
+    [aside Nested blocks too.]
+    The end.
+
+]
+[title Just a heading]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<section class='section'>\
+        <p>Hello to everyone who is reading this. This sentence should be on the same line.</p>\
+        <p>Although, this one will not be.</p>\
+        <ul><li>Lists stay out of paragraphs. </li></ul>\
+        <aside class='aside'>Nested blocks too.</aside>\
+        <p>The end.</p>\
+        </section>\
+        <h1 class='title' id='just-a-heading'>Just a heading</h1>"
+    );
+    assert_eq!(
+        TextRenderer.render(&document),
+        "\
+Hello to everyone who is reading this. This sentence should be on the same line.
+
+Although, this one will not be.
+- Lists stay out of paragraphs.
+Nested blocks too.
+The end.
+Just a heading
+"
+    );
+
+    let mut ctx = Context::create();
+    ctx.legacy_breaks = true;
+    let document = parse_document("[a One\n\nTwo]", &mut ctx).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>One <br><br>Two</div>"
+    );
+}
+
+#[test]
+fn test_comments() {
+    let my_osml = r"
+%% Between blocks, [a not a block]
+%{ Over
+   [b multiple] lines }%
+[a One %% the rest of the line
+   two%{ inline }% three 100\% %{
+   spanning }% four
+   [meta title=Hi %% here too
+   ]
+]
+[table
+%% a row
+| x |
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>One two three 100% four </div>\
+        <table><tbody><tr><td>x</td></tr></tbody></table>"
+    );
+    assert_eq!(document.meta.title.as_deref(), Some("Hi"));
+
+    assert!(matches!(
+        parse_document("[a %{ never closed]", &mut Context::create()),
+        Err(Error {
+            error: ErrorType::UnclosedComment,
+            ..
+        })
+    ));
+
+    //  The rest of the line a comment closes on carries on the text or item it started in.
+    let render = |s: &str| HtmlRenderer.render(&parse_document(s, &mut Context::create()).unwrap());
+    assert_eq!(render("[a x %{\n}% + y]"), "<div class='a'>x + y</div>");
+    assert_eq!(
+        render("[a\n+ one %{\nc }% still one\n+ two\n]"),
+        "<div class='a'><ul><li>one still one </li><li>two </li></ul></div>"
+    );
+}
+
+#[test]
+fn test_multiline_formatting() {
+    let my_osml = r"
+[section
+    Some *bold text that
+    keeps going* and /italics
+    over _three
+    lines_/ too.
+
+    Next paragraph.
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<section class='section'>\
+        <p>Some <b>bold text that keeps going</b> and <i>italics over <u>three lines</u></i> too.</p>\
+        <p>Next paragraph.</p>\
+        </section>"
+    );
+
+    //  Points at the opening delimiter, not where the paragraph ends.
+    assert!(matches!(
+        parse_document(
+            "[a one\n  two *three\n  four\n\n  five]",
+            &mut Context::create()
+        ),
+        Err(Error {
+            error: ErrorType::UnclosedBold,
+            location: Location::Span(Span {
+                start: Position { line: 1, pos: 6 },
+                ..
+            }),
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse_document("[a one\n  _two\n  three]", &mut Context::create()),
+        Err(Error {
+            error: ErrorType::UnclosedUnderline,
+            location: Location::Span(Span {
+                start: Position { line: 1, pos: 2 },
+                ..
+            }),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_urls_and_paths() {
+    let my_osml = r"
+[a
+    See https://example.com/a/b_c?q=1, or *(https://example.com/x)*.
+    Edit src/index.osml and/or /usr/bin, 1/2 of /the/ time.
+    [link https://example.com/a/b Not https://example.org]
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>See <a href='https://example.com/a/b_c?q=1'>https://example.com/a/b_c?q=1</a>, \
+        or <b>(</b><a href='https://example.com/x'>https://example.com/x</a><b>)</b>. \
+        Edit src/index.osml and/or /usr/bin, 1/2 of <i>the</i> time. \
+        <a href='https://example.com/a/b'>Not https://example.org</a></div>"
+    );
+    assert_eq!(
+        MarkdownRenderer.render(
+            &parse_document("[a Go to https://example.com.]", &mut Context::create()).unwrap()
+        ),
+        "Go to [https://example.com](<https://example.com>).\n"
+    );
+    //  Delimiters at the end only stay out of the address when they close formatting opened before it.
+    assert_eq!(
+        HtmlRenderer.render(
+            &parse_document(
+                "[a (see https://example.com/*a*). http://x.com/*bold* *see http://y.com*]",
+                &mut Context::create()
+            )
+            .unwrap()
+        ),
+        "<div class='a'>(see <a href='https://example.com/*a*'>https://example.com/*a*</a>). \
+        <a href='http://x.com/*bold*'>http://x.com/*bold*</a> \
+        <b>see </b><a href='http://y.com'>http://y.com</a></div>"
+    );
+
+    let mut ctx = Context::create();
+    ctx.delimiters.remove(&'_');
+    ctx.delimiters.insert('^', Format::Underline);
+    let document = parse_document(r"[a snake_case ^under^ \^]", &mut ctx).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>snake_case <u>under</u> ^</div>"
+    );
+    let err = parse_document("[a ^open]", &mut ctx).unwrap_err();
+    assert_eq!(
+        err.error.message_in(&ctx),
+        "Opening `^` must be matched with a closing `^`. Or, you meant to escape the `^` with `\\^`."
+    );
+    assert!(ErrorType::UnclosedUnderline.message().contains("`_`"));
+}
+
+#[test]
+fn test_mixed_lists() {
+    let my_osml = r"
+[a
+    + one
+    == two
+    == three
+    +++ four
+    ++ five
+    = six
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>\
+        <ul><li>one \
+            <ol><li>two </li><li>three \
+                <ul><li>four </li></ul>\
+            </li></ol>\
+            <ul><li>five </li></ul>\
+        </li></ul>\
+        <ol><li>six </li></ol>\
+        </div>"
+    );
+    assert_eq!(
+        MarkdownRenderer.render(&document),
+        "\
+- one
+    1. two
+    2. three
+        - four
+    - five
+
+1. six
+"
+    );
+
+    assert!(matches!(
+        parse_document("[a + one\n=== two]", &mut Context::create()),
+        Err(Error {
+            error: ErrorType::InvalidListDepth,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_list_kinds() {
+    let my_osml = r"
+[a
+    + [x] Done
+    + [ ] Not *yet,
+      but* soon
+      [aside Still in the item.]
+    ++ nested
+    + [x]plain
+
+    : Term
+        The definition,
+        over two lines.
+    : Another *term*
+        Its definition.
+    After.
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>\
+        <ul>\
+            <li class='task'><input type='checkbox' disabled checked> Done </li>\
+            <li class='task'><input type='checkbox' disabled> Not <b>yet, but</b> soon \
+                <aside class='aside'>Still in the item.</aside>\
+                <ul><li>nested </li></ul>\
+            </li>\
+            <li><div class='x'></div>plain </li>\
+        </ul>\
+        <dl>\
+            <dt>Term</dt><dd>The definition, over two lines.</dd>\
+            <dt>Another <b>term</b></dt><dd>Its definition.</dd>\
+        </dl>\
+        <p>After.</p>\
+        </div>"
+    );
+    assert_eq!(
+        MarkdownRenderer.render(
+            &parse_document(
+                "[a\n+ [x] done\n+ [ ] to do\n\n: Term\n  Meaning\n]",
+                &mut Context::create()
+            )
+            .unwrap()
+        ),
+        "- [x] done\n- [ ] to do\n\nTerm\n: Meaning\n"
+    );
+
+    //  Without an indented definition below it, a line starting with `:` is text.
+    let document =
+        parse_document("[a :)]\n[b\n: not a term\nplain\n]", &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>:)</div><div class='b'>: not a term plain </div>"
+    );
+}
+
+#[test]
+fn test_footnotes() {
+    let my_osml = r"
+[footnote later Defined before it's used.]
+[a
+    One[^first] and *two[^later]*, one again[^first].
+]
+[footnote first The *first* note[^nested].]
+[footnote nested Notes can have notes.]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>\
+        One<sup class='footnote-ref'><a href='#fn-1' id='fnref-1'>1</a></sup> and \
+        <b>two</b><sup class='footnote-ref'><a href='#fn-2' id='fnref-2'>2</a></sup>, \
+        one again<sup class='footnote-ref'><a href='#fn-1' id='fnref-1-2'>1</a></sup>. \
+        </div>\
+        <section class='footnotes'><ol>\
+        <li id='fn-1'>The <b>first</b> note\
+            <sup class='footnote-ref'><a href='#fn-3' id='fnref-3'>3</a></sup>. \
+            <a href='#fnref-1' class='footnote-back'>↩</a> \
+            <a href='#fnref-1-2' class='footnote-back'>↩</a></li>\
+        <li id='fn-2'>Defined before it&#39;s used. <a href='#fnref-2' class='footnote-back'>↩</a></li>\
+        <li id='fn-3'>Notes can have notes. <a href='#fnref-3' class='footnote-back'>↩</a></li>\
+        </ol></section>"
+    );
+    assert_eq!(
+        MarkdownRenderer.render(&document),
+        "\
+One[^1] and **two**[^2], one again[^1].
+
+[^1]: The **first** note[^3].
+[^2]: Defined before it's used.
+[^3]: Notes can have notes.
+"
+    );
+
+    let (document, errors) = parse_document_recover(
+        "[a Missing[^nope].]\n[footnote unused Never referenced.]\n[footnote unused Again.]",
+        &mut Context::create(),
+    );
+    let errors: Vec<_> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(errors, ["DuplicateFootnote", "UndefinedFootnote"]);
+    assert!(matches!(
+        &document.warnings[..],
+        [Error {
+            error: ErrorType::UnusedFootnote,
+            location: Location::Span(Span {
+                start: Position { line: 1, pos: 0 },
+                ..
+            }),
+            ..
+        }]
+    ));
+    assert!(document.footnotes.is_empty());
+}
+
+#[test]
+fn test_toc() {
+    let my_osml = r"
+[toc]
+[title Crème brûlée, *part* 2!]
+[subtitle Notes]
+[deep Deep]
+[subtitle Notes]
+[subtitle#custom Mine]
+[section#notes-1 Taken by hand.]
+[subtitle Notes]
+";
+
+    let mut ctx = Context::create();
+    ctx.elements.insert("deep".to_string(), "h4".to_string());
+    let document = parse_document(my_osml, &mut ctx).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<nav class='toc'><ul>\
+            <li><a href='#crème-brûlée-part-2'>Crème brûlée, part 2!</a>\
+            <ul>\
+                <li><a href='#notes'>Notes</a><ul><li><a href='#deep'>Deep</a></li></ul></li>\
+                <li><a href='#notes-2'>Notes</a></li>\
+                <li><a href='#custom'>Mine</a></li>\
+                <li><a href='#notes-3'>Notes</a></li>\
+            </ul></li>\
+        </ul></nav>\
+        <h1 class='title' id='crème-brûlée-part-2'>Crème brûlée, <b>part</b> 2!</h1>\
+        <h2 class='subtitle' id='notes'>Notes</h2>\
+        <h4 class='deep' id='deep'>Deep</h4>\
+        <h2 class='subtitle' id='notes-2'>Notes</h2>\
+        <h2 class='subtitle' id='custom'>Mine</h2>\
+        <section class='section' id='notes-1'>Taken by hand.</section>\
+        <h2 class='subtitle' id='notes-3'>Notes</h2>"
+    );
+    assert_eq!(document.outline.len(), 6);
+    assert_eq!(
+        document.outline[0],
+        Heading {
+            level: 1,
+            id: "crème-brûlée-part-2".to_string(),
+            text: "Crème brûlée, part 2!".to_string(),
+        }
+    );
+    assert_eq!(slug("  Hello,  World -- again_"), "hello-world-again");
+    assert_eq!(slug("?!"), "heading");
+}
+
+#[test]
+fn test_variables() {
+    let my_osml = r"
+[let product Big *Thing* \]]
+[a Welcome to {{product}} {{ version }}, see {{dir}}/{{file}} or mail {{email}}. \{{not}}]
+[let version 2.0]
+[b Now {{version}}.]
+";
+
+    let mut ctx = Context::create();
+    ctx.variables.extend([
+        ("version".to_string(), "1.0".to_string()),
+        ("dir".to_string(), "docs".to_string()),
+        ("file".to_string(), "faq.osml".to_string()),
+        ("email".to_string(), "help@example.com".to_string()),
+    ]);
+    let document = parse_document(my_osml, &mut ctx).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'>Welcome to Big *Thing* ] 1.0, see docs/faq.osml or mail help@example.com. {{not}}</div>\
+        <div class='b'>Now 2.0.</div>"
+    );
+    assert_eq!(ctx.variables["version"], "1.0");
+
+    assert!(matches!(
+        parse_document("[a\n  Hi {{nobody}}\n]", &mut Context::create()),
+        Err(Error {
+            error: ErrorType::UndefinedVariable,
+            location: Location::Span(Span {
+                start: Position { line: 1, pos: 5 },
+                end: Position { line: 1, pos: 15 },
+            }),
+            ..
+        })
+    ));
+    let (_, errors) =
+        parse_document_recover("[let 1x y]\n[a {{a b}} {{open]", &mut Context::create());
+    let errors: Vec<_> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(errors, ["BadVariable", "BadVariable", "BadVariable"]);
+}
+
+#[test]
+fn test_macros() {
+    let my_osml = r"
+[let site Docs]
+[macro callout kind body
+    [aside.{{kind}} *{{kind}}:* {{body}} ({{site}})]
+]
+[macro rule
+    [hr]
+]
+[page
+    [callout warning Mind the /gap/
+    between the [link /map.html map] and the train.]
+    [rule]
+]
+";
+
+    let document = parse_document(my_osml, &mut Context::create()).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='page'>\
+        <aside class='aside warning'><b>warning:</b> Mind the <i>gap</i> \
+        between the <a href='/map.html'>map</a> and the train. (Docs)</aside>\
+        <div class='hr'></div>\
+        </div>"
+    );
+
+    //  Brackets in comments and code don't end the template.
+    let document = parse_document(
+        "[macro snippet\n[code lang=rust ``` let s = \"]\"; ```]\n%% a ] here\n%{ and ] here }% Done.\n]\n\
+        [a [snippet]]",
+        &mut Context::create(),
+    )
+    .unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'><pre><code class='language-rust'> <span class='hl-keyword'>let</span> s = \
+        <span class='hl-string'>&quot;]&quot;</span>; </code></pre><p>Done.</p></div>"
+    );
+
+    //  Arguments can be blocks of their own.
+    let document = parse_document(
+        "[macro wrap first rest\n[w {{first}} {{rest}}]\n]\n[a [wrap [b x \\] y] and more]]",
+        &mut Context::create(),
+    )
+    .unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'><div class='w'><div class='b'>x ] y</div><p>and more</p></div></div>"
+    );
+
+    //  Errors in the expansion point at the call, with the definition as a note.
+    let err = parse_document(
+        "[macro loop x\n[loop {{x}}]\n]\n[a [loop 1]]",
+        &mut Context::create(),
+    )
+    .unwrap_err();
+    assert!(matches!(err.error, ErrorType::MacroRecursion));
+    assert!(matches!(
+        err.location,
+        Location::Span(Span {
+            start: Position { line: 3, pos: 3 },
+            end: Position { line: 3, pos: 11 },
+        })
+    ));
+    let note = err.note.unwrap();
+    assert!(matches!(note.error, ErrorType::MacroDefinedHere));
+    assert!(matches!(
+        note.location,
+        Location::Span(Span {
+            start: Position { line: 0, pos: 0 },
+            end: Position { line: 2, pos: 1 },
+        })
+    ));
+
+    let (_, errors) = parse_document_recover(
+        "[macro pair a b\n{{a}}/{{b}}\n]\n[macro code\n]\n[x [pair]]",
+        &mut Context::create(),
+    );
+    let errors: Vec<_> = errors.iter().map(|e| format!("{:?}", e.error)).collect();
+    assert_eq!(errors, ["BadMacro", "MacroArguments"]);
+
+    //  Each macro using the next one twice would expand 2^23 times.
+    let name = |i: u8| format!("m{}", (b'a' + i) as char);
+    let mut chain: String = (0..23)
+        .map(|i| {
+            format!(
+                "[macro {}\n[{next}][{next}]\n]\n",
+                name(i),
+                next = name(i + 1)
+            )
+        })
+        .collect();
+    chain.push_str(&format!(
+        "[macro {}\nLots of text.\n]\n[a [{}]]",
+        name(23),
+        name(0)
+    ));
+    assert!(matches!(
+        parse_document(&chain, &mut Context::create()),
+        Err(Error {
+            error: ErrorType::MacroTooLarge,
+            ..
+        })
+    ));
+    let (_, errors) = parse_document_recover(&chain, &mut Context::create());
+    assert!(errors
+        .iter()
+        .all(|e| matches!(e.error, ErrorType::MacroTooLarge)));
+
+    //  Macros go both ways across includes, and notes point at the file the macro is defined in.
+    let dir = std::env::temp_dir().join(format!("osml_test_macros_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("partials")).unwrap();
+    let write = |name: &str, s: &str| std::fs::write(dir.join(name), s).unwrap();
+    write(
+        "page.osml",
+        "[macro shout text\n*{{text}}*\n]\n[include partials/macros.osml]\n[a [callout note Hi]]",
+    );
+    write(
+        "partials/macros.osml",
+        "[macro callout kind body\n[aside.{{kind}} [shout {{body}}]]\n]\n[macro broken\n[b *x]\n]",
+    );
+    let mut ctx = Context::create();
+    ctx.path = Some(dir.join("page.osml"));
+    let source = std::fs::read_to_string(dir.join("page.osml")).unwrap();
+    let document = parse_document(&source, &mut ctx).unwrap();
+    assert_eq!(
+        HtmlRenderer.render(&document),
+        "<div class='a'><aside class='aside note'><b>Hi</b></aside></div>"
+    );
+    let (_, errors) = parse_document_recover(&(source + " [a [broken]]"), &mut ctx);
+    assert!(matches!(
+        &errors[..],
+        [Error {
+            error: ErrorType::UnclosedBold,
+            file: None,
+            note: Some(note),
+            ..
+        }] if note.file == Some(dir.join("partials/macros.osml"))
+    ));
+
+    //  Including a file halfway doesn't reset how deep macros are.
+    let chain = |prefix: char, last: &str| -> String {
+        (0..20u8)
+            .map(|i| {
+                let body = match i {
+                    19 => last.to_string(),
+                    _ => format!("[{}{}]", prefix, (b'a' + i + 1) as char),
+                };
+                format!("[macro {}{}\n{}\n]\n", prefix, (b'a' + i) as char, body)
+            })
+            .collect()
+    };
+    write("partials/inner.osml", "[x [pa]]");
+    let source = chain('p', "end") + &chain('o', "[include partials/inner.osml]") + "[a [oa]]";
+    let (_, errors) = parse_document_recover(&source, &mut ctx);
+    assert!(matches!(
+        &errors[..],
+        [Error {
+            error: ErrorType::MacroRecursion,
+            ..
+        }]
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
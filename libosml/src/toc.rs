@@ -0,0 +1,129 @@
+use super::{
+    insert_list_item, plain_text, set_attribute, Block, Document, Heading, Inline, Link, ListItem,
+    Node, Span,
+};
+
+//  Gives every heading an id to link to, unless it was given one, and fills `[toc]` blocks with links to them.
+//  Runs once everything is parsed, so that a table of contents can come before its headings.
+pub(crate) fn resolve_headings(document: &mut Document) {
+    //  Ids written out by hand are taken first, wherever they are.
+    let mut used = Vec::new();
+    collect_ids(&mut document.children, &mut used);
+    let mut outline = Vec::new();
+    assign_ids(&mut document.children, &mut used, &mut outline);
+    fill_tocs(&mut document.children, &outline);
+    document.outline = outline;
+}
+
+//  `Crème brûlée, part 2!` becomes `crème-brûlée-part-2`.
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    let mut dash = false;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if dash && !slug.is_empty() {
+                slug.push('-');
+            }
+            dash = false;
+            slug.extend(c.to_lowercase());
+        } else if c.is_whitespace() || c == '-' || c == '_' {
+            dash = true;
+        }
+    }
+    if slug.is_empty() {
+        slug.push_str("heading");
+    }
+    slug
+}
+
+fn heading_level(block: &Block) -> Option<usize> {
+    let level = block.element.as_deref()?.strip_prefix('h')?.parse().ok()?;
+    Some(level).filter(|level| (1..=6).contains(level))
+}
+
+fn collect_ids(nodes: &mut [Node], used: &mut Vec<String>) {
+    for node in nodes {
+        if let Node::Block(block) = node {
+            used.extend(block.attribute("id").map(str::to_string));
+        }
+        for children in node.children_mut() {
+            collect_ids(children, used);
+        }
+    }
+}
+
+fn assign_ids(nodes: &mut [Node], used: &mut Vec<String>, outline: &mut Vec<Heading>) {
+    for node in nodes {
+        if let Node::Block(block) = node {
+            if let Some(level) = heading_level(block) {
+                let mut text = String::new();
+                plain_text(&block.children, &mut text);
+                let id = match block.attribute("id") {
+                    Some(id) => id.to_string(),
+                    None => {
+                        //  Repeated headings become `notes`, `notes-1`, `notes-2` and so on.
+                        let base = slug(&text);
+                        let mut id = base.clone();
+                        let mut n = 0;
+                        while used.contains(&id) {
+                            n += 1;
+                            id = format!("{}-{}", base, n);
+                        }
+                        used.push(id.clone());
+                        set_attribute(&mut block.attributes, "id", id.clone());
+                        id
+                    }
+                };
+                outline.push(Heading {
+                    level,
+                    id,
+                    text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+                });
+            }
+        }
+        for children in node.children_mut() {
+            assign_ids(children, used, outline);
+        }
+    }
+}
+
+fn fill_tocs(nodes: &mut [Node], outline: &[Heading]) {
+    for node in nodes {
+        match node {
+            Node::Block(block) if block.name == "toc" => {
+                block.children.extend(outline_list(outline, block.span));
+            }
+            _ => node
+                .children_mut()
+                .into_iter()
+                .for_each(|children| fill_tocs(children, outline)),
+        }
+    }
+}
+
+//  Nested by level, starting from the highest one used. Skipped levels only nest one deeper.
+fn outline_list(outline: &[Heading], span: Span) -> Option<Node> {
+    let top = outline.iter().map(|heading| heading.level).min()?;
+    let mut nodes = Vec::new();
+    let mut last_depth = 0;
+    for heading in outline {
+        let depth = (heading.level - top + 1).min(last_depth + 1);
+        last_depth = depth;
+        let link = Link {
+            attributes: Vec::new(),
+            href: format!("#{}", heading.id),
+            children: vec![Node::Inline(Inline::Text {
+                text: heading.text.clone(),
+                span,
+            })],
+            span,
+        };
+        let item = ListItem {
+            checked: None,
+            children: vec![Node::Link(link)],
+            span,
+        };
+        insert_list_item(&mut nodes, 1, depth, false, item);
+    }
+    nodes.pop()
+}
@@ -0,0 +1,81 @@
+use super::{is_whitespace, key_len, Error, ErrorType, Line, Parser, Pos, Position, Result, Span};
+
+//  `[let version 1.2.3]`, the value being the rest of the line up to the `]`, with escapes.
+//  Only text after it sees the value, a later `[let ...]` of the same name replaces it.
+//  Expects the character after the name.
+pub fn parse_let(p: &mut Parser, line: Line, mut pos: Pos, start: Position) -> Result<(Line, Pos)> {
+    let vline = &p.lines[line];
+    while vline.get(pos).is_some_and(|&c| is_whitespace(c)) {
+        pos += 1;
+    }
+    let name_start = pos.min(vline.len());
+    let len = key_len(&vline[name_start..]);
+    let name: String = vline[name_start..name_start + len].iter().collect();
+    pos = name_start + len;
+    let name_ends = vline.get(pos).is_none_or(|&c| is_whitespace(c) || c == ']');
+
+    let mut value = String::new();
+    let mut escaped = false;
+    while let Some(&c) = vline.get(pos) {
+        match c {
+            _ if escaped => {
+                value.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            ']' => break,
+            _ => value.push(c),
+        }
+        pos += 1;
+    }
+    //  Like `[include ...]`, the value can't go over multiple lines.
+    let end = if pos < vline.len() {
+        (line, pos + 1)
+    } else {
+        p.recover(Error::at(start, ErrorType::BlockNoEnd))?;
+        (line + 1, 0)
+    };
+
+    if name.is_empty() || !name_ends {
+        p.recover(Error::span(
+            Span::new(start, Position::new(end.0, end.1)),
+            ErrorType::BadVariable,
+        ))?;
+        return Ok(end);
+    }
+    p.variables.insert(name, value.trim().to_string());
+    Ok(end)
+}
+
+//  `{{name}}`, with spaces allowed inside the braces. Returns the text to put in its place.
+//  When recovering, variables that can't be found are left in the text as written.
+//  Expects the first `{`.
+pub(crate) fn parse_interpolation(p: &mut Parser, line: Line, pos: Pos) -> Result<(String, Pos)> {
+    let vline = &p.lines[line];
+    let start = Position::new(line, pos);
+    let Some(close) =
+        (pos + 2..vline.len().saturating_sub(1)).find(|&i| vline[i] == '}' && vline[i + 1] == '}')
+    else {
+        p.recover(Error::span(
+            Span::new(start, Position::new(line, pos + 2)),
+            ErrorType::BadVariable,
+        ))?;
+        return Ok(("{{".to_string(), pos + 2));
+    };
+    let raw: String = vline[pos..close + 2].iter().collect();
+    let name = raw[2..raw.len() - 2].trim().to_string();
+    let span = Span::new(start, Position::new(line, close + 2));
+
+    let chars: Vec<char> = name.chars().collect();
+    if chars.is_empty() || key_len(&chars) != chars.len() {
+        p.recover(Error::span(span, ErrorType::BadVariable))?;
+        return Ok((raw, close + 2));
+    }
+    match p.variables.get(&name) {
+        Some(value) => Ok((value.clone(), close + 2)),
+        None => {
+            p.recover(Error::span(span, ErrorType::UndefinedVariable))?;
+            Ok((raw, close + 2))
+        }
+    }
+}
@@ -1,5 +1,8 @@
-use colored::Colorize;
-use libosml::{parse, Context, Error, Location};
+use colored::{ColoredString, Colorize};
+use libosml::{
+    parse_document_recover, Context, Error, HtmlRenderer, Location, MarkdownRenderer, Renderer,
+    Span, Template, TextRenderer,
+};
 use std::fs;
 
 fn help_and_exit() -> ! {
@@ -12,6 +15,13 @@ Usage:
 
 Options:
     -o              Specify your output.
+    -t | --target   Output format: html (default), text, or markdown.
+    --template      Page layout to place the output into at `{{{{content}}}}`.
+    -e | --element  Render blocks called `name` as `<tag>`, given as `name=tag`.
+    -D | --define   Set the variable `{{{{name}}}}` used in text, given as `name=value`.
+    -m | --meta     Print the document's metadata and included files to stdout.
+    -W | --deny-warnings
+                    Fail on warnings, such as images without alt text.
     -c | --color    Forces color 24/7 100% of the time.
     -l | --lame     For Lame people who don't like color. *
     -d | --dryrun   Don't actually write to output.
@@ -46,11 +56,43 @@ fn main() {
     run(&cli(args))
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Html,
+    Text,
+    Markdown,
+}
+
+impl Target {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "html" => Some(Self::Html),
+            "text" | "txt" => Some(Self::Text),
+            "markdown" | "md" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    fn renderer(&self) -> Box<dyn Renderer> {
+        match self {
+            Self::Html => Box::new(HtmlRenderer),
+            Self::Text => Box::new(TextRenderer),
+            Self::Markdown => Box::new(MarkdownRenderer),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RunContext {
     color: Option<()>,
     lame: bool,
     dryrun: bool,
+    deny_warnings: bool,
+    meta: bool,
+    target: Target,
+    template: Option<String>,
+    elements: Vec<(String, String)>,
+    variables: Vec<(String, String)>,
     input: String,
     output: String,
 }
@@ -60,20 +102,79 @@ fn cli(args: Vec<String>) -> RunContext {
         color: None,
         lame: false,
         dryrun: false,
+        deny_warnings: false,
+        meta: false,
+        target: Target::Html,
+        template: None,
+        elements: Vec::new(),
+        variables: Vec::new(),
         input: String::new(),
         output: String::new(),
     };
 
     let mut inputs = Vec::new();
     let mut outputs = Vec::new();
+    let mut targets = Vec::new();
+    let mut templates = Vec::new();
 
+    let mut error = false;
     let mut was_o_flag = false;
+    let mut was_t_flag = false;
+    let mut was_template_flag = false;
+    let mut was_e_flag = false;
+    let mut was_d_flag = false;
     for arg in args.iter() {
         match arg.as_str() {
             "-l" | "--lame" => ctx.lame = true,
             "-c" | "--color" => ctx.color = Some(()),
             "-d" | "-dryrun" => ctx.dryrun = true,
+            "-m" | "--meta" => ctx.meta = true,
+            "-W" | "--deny-warnings" => ctx.deny_warnings = true,
             "-o" => was_o_flag = true,
+            "-t" | "--target" => was_t_flag = true,
+            "--template" => was_template_flag = true,
+            "-e" | "--element" => was_e_flag = true,
+            "-D" | "--define" => was_d_flag = true,
+            _ if was_t_flag => {
+                targets.push(arg.clone());
+                was_t_flag = false;
+            }
+            _ if was_template_flag => {
+                templates.push(arg.clone());
+                was_template_flag = false;
+            }
+            _ if was_e_flag => {
+                match arg.split_once('=') {
+                    Some((name, element)) => {
+                        ctx.elements.push((name.to_string(), element.to_string()))
+                    }
+                    None => {
+                        eprintln!(
+                            "{} Elements are given as `name=tag`, not `{}`",
+                            "Error:".red().bold(),
+                            arg.yellow()
+                        );
+                        error = true;
+                    }
+                }
+                was_e_flag = false;
+            }
+            _ if was_d_flag => {
+                match arg.split_once('=') {
+                    Some((name, value)) => {
+                        ctx.variables.push((name.to_string(), value.to_string()))
+                    }
+                    None => {
+                        eprintln!(
+                            "{} Variables are given as `name=value`, not `{}`",
+                            "Error:".red().bold(),
+                            arg.yellow()
+                        );
+                        error = true;
+                    }
+                }
+                was_d_flag = false;
+            }
             _ if was_o_flag => {
                 outputs.push(arg.clone());
             }
@@ -83,7 +184,6 @@ fn cli(args: Vec<String>) -> RunContext {
         }
     }
 
-    let mut error = false;
     if inputs.len() != 1 {
         if inputs.is_empty() {
             eprintln!("{} No inputs given", "Error:".red().bold());
@@ -93,7 +193,7 @@ fn cli(args: Vec<String>) -> RunContext {
                 "Error:".red().bold()
             );
             inputs.iter().for_each(|i| eprint!("`{}` ", i.yellow()));
-            eprint!(".\n");
+            eprintln!(".");
         }
         error = true;
     }
@@ -107,8 +207,40 @@ fn cli(args: Vec<String>) -> RunContext {
                 "Error:".red().bold()
             );
             outputs.iter().for_each(|i| eprint!("`{}` ", i.yellow()));
-            eprint!(".\n");
+            eprintln!(".");
+        }
+        error = true;
+    }
+
+    if targets.len() > 1 {
+        eprint!(
+            "{} Multiple targets given, including: ",
+            "Error:".red().bold()
+        );
+        targets.iter().for_each(|i| eprint!("`{}` ", i.yellow()));
+        eprintln!(".");
+        error = true;
+    } else if let Some(target) = targets.first() {
+        match Target::from_name(target) {
+            Some(target) => ctx.target = target,
+            None => {
+                eprintln!(
+                    "{} Unknown target `{}`",
+                    "Error:".red().bold(),
+                    target.yellow()
+                );
+                error = true;
+            }
         }
+    }
+
+    if templates.len() > 1 {
+        eprint!(
+            "{} Multiple templates given, including: ",
+            "Error:".red().bold()
+        );
+        templates.iter().for_each(|i| eprint!("`{}` ", i.yellow()));
+        eprintln!(".");
         error = true;
     }
 
@@ -116,15 +248,16 @@ fn cli(args: Vec<String>) -> RunContext {
         help_and_exit();
     }
 
-    ctx.input = inputs.get(0).unwrap().clone();
-    ctx.output = outputs.get(0).unwrap().clone();
+    ctx.template = templates.first().cloned();
+    ctx.input = inputs.first().unwrap().clone();
+    ctx.output = outputs.first().unwrap().clone();
 
     ctx
 }
 
 fn run(ctx: &RunContext) {
     //  --lame should always have precedent over --color
-    if let Some(_) = ctx.color {
+    if ctx.color.is_some() {
         colored::control::set_override(true)
     }
     if ctx.lame {
@@ -141,11 +274,72 @@ fn run(ctx: &RunContext) {
         std::process::exit(1)
     });
 
-    let parsed = parse(input.clone(), Context::create()).unwrap_or_else(|e| {
-        let lines = input.split('\n').map(|s| s.to_string()).collect();
-        print_error(&ctx.input, lines, e);
+    let mut osml_ctx = Context::create();
+    osml_ctx.path = Some(ctx.input.clone().into());
+    osml_ctx.elements.extend(ctx.elements.iter().cloned());
+    osml_ctx.variables.extend(ctx.variables.iter().cloned());
+    if let Some(template) = &ctx.template {
+        let source = fs::read_to_string(template).unwrap_or_else(|e| {
+            eprintln!(
+                "{} Couldn't open template file: `{}`, {}",
+                "Error:".red().bold(),
+                template.yellow(),
+                e
+            );
+            std::process::exit(1)
+        });
+        osml_ctx.template = Some(Template::new(source).unwrap_or_else(|e| {
+            eprintln!(
+                "{} {} In `{}`.",
+                "Error:".red().bold(),
+                e.error.message().bold(),
+                template.yellow()
+            );
+            std::process::exit(1)
+        }));
+    }
+
+    let (document, errors) = parse_document_recover(&input, &mut osml_ctx);
+    let lines = lines_of(&input);
+    //  Problems in included files are shown against that file.
+    let show = |e: Error, label: ColoredString| match &e.file {
+        Some(file) => {
+            let source = fs::read_to_string(file).unwrap_or_default();
+            let file = file.display().to_string();
+            print_error(&file, &lines_of(&source), e, label, &osml_ctx)
+        }
+        None => print_error(&ctx.input, &lines, e, label, &osml_ctx),
+    };
+    //  Errors out of macros also point at where the macro is defined.
+    let report = |mut e: Error, is_warning: bool| {
+        let note = e.note.take();
+        let label = if is_warning {
+            "Warning:".yellow().bold()
+        } else {
+            "Error:".red().bold()
+        };
+        show(e, label);
+        if let Some(note) = note {
+            show(*note, "Note:".cyan().bold());
+        }
+    };
+    let failed = !errors.is_empty() || (ctx.deny_warnings && !document.warnings.is_empty());
+    errors.into_iter().for_each(|e| report(e, false));
+    document
+        .warnings
+        .iter()
+        .cloned()
+        .for_each(|e| report(e, true));
+    if failed {
         std::process::exit(1);
-    });
+    }
+    let content = ctx.target.renderer().render(&document);
+    //  Only HTML gets a page around it when no template is given.
+    let parsed = match (osml_ctx.template, ctx.target) {
+        (Some(template), _) => template.apply(&content, &document.meta),
+        (None, Target::Html) => Template::default().apply(&content, &document.meta),
+        (None, _) => content,
+    };
 
     if !ctx.dryrun {
         fs::write(&ctx.output, parsed).unwrap_or_else(|e| {
@@ -158,36 +352,93 @@ fn run(ctx: &RunContext) {
             std::process::exit(1)
         });
     }
+
+    //  Picked up by `osmlmk`.
+    if ctx.meta {
+        println!(
+            "{}",
+            ron::to_string(&(&document.meta, &document.includes)).unwrap()
+        );
+    }
+}
+
+fn lines_of(s: &str) -> Vec<String> {
+    s.split('\n').map(|s| s.to_string()).collect()
 }
 
-fn print_error(file: &String, lines: Vec<String>, Error { error, location }: Error) {
-    match location {
+fn print_error(
+    file: &String,
+    lines: &[String],
+    Error {
+        error, location, ..
+    }: Error,
+    label: ColoredString,
+    osml_ctx: &Context,
+) {
+    let (line, span) = match location {
         Location::Null => unreachable!("Location::Null is only used internally"),
-        Location::Absolute(line) => {
-            let line_number_spaces = (0..(line.to_string().len()))
-                .map(|_| ' ')
-                .collect::<String>();
-
-            eprintln!("{} {}", "Error:".red().bold(), error.message().bold());
-            eprintln!("  {} --> {}", line_number_spaces, file);
-            peek_print_error_line(&lines, line, -2, &line_number_spaces);
-            peek_print_error_line(&lines, line, -1, &line_number_spaces);
-            eprintln!(
-                "  {} {} {}",
-                (line + 1).to_string().blue().bold(),
-                "|".cyan().bold(),
-                lines.get(line).unwrap()
-            );
-            peek_print_error_line(&lines, line, 1, &line_number_spaces);
-            peek_print_error_line(&lines, line, 2, &line_number_spaces);
-        }
+        Location::Absolute(line) => (line, None),
+        Location::Span(span) => (span.start.line, Some(span)),
+    };
+    let line_number_spaces = (0..(line.to_string().len()))
+        .map(|_| ' ')
+        .collect::<String>();
+
+    eprintln!("{} {}", label, error.message_in(osml_ctx).bold());
+    if let Some(span) = span {
+        eprintln!(
+            "  {} --> {}:{}:{}",
+            line_number_spaces,
+            file,
+            line + 1,
+            span.start.pos + 1
+        );
+    } else {
+        eprintln!("  {} --> {}", line_number_spaces, file);
     }
+    peek_print_error_line(lines, line, -2, &line_number_spaces);
+    peek_print_error_line(lines, line, -1, &line_number_spaces);
+    eprintln!(
+        "  {} {} {}",
+        (line + 1).to_string().blue().bold(),
+        "|".cyan().bold(),
+        lines.get(line).unwrap()
+    );
+    if let Some(span) = span {
+        print_error_underline(lines, span, &line_number_spaces);
+    }
+    peek_print_error_line(lines, line, 1, &line_number_spaces);
+    peek_print_error_line(lines, line, 2, &line_number_spaces);
+}
+
+//  Spans that run over multiple lines are underlined until the end of their first line.
+fn print_error_underline(lines: &[String], span: Span, line_number_spaces: &String) {
+    let vline = lines.get(span.start.line).unwrap();
+    //  Tabs are kept so that the underline stays lined up with the line above it.
+    let padding = vline
+        .chars()
+        .take(span.start.pos)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let end = if span.end.line == span.start.line {
+        span.end.pos
+    } else {
+        vline.chars().count()
+    };
+    let underline = "^".repeat(end.saturating_sub(span.start.pos).max(1));
+    eprintln!(
+        "  {} {} {}{}",
+        line_number_spaces,
+        "|".blue().bold(),
+        padding,
+        underline.red().bold()
+    );
 }
 
-fn peek_print_error_line(lines: &Vec<String>, line: usize, inc: isize, line_number_spaces: &String) {
+fn peek_print_error_line(lines: &[String], line: usize, inc: isize, line_number_spaces: &String) {
     let last_line = (|| {
         let idx: usize = (line as isize + inc).try_into().ok()?;
-        Some(lines.get(idx)?)
+        lines.get(idx)
     })()
     .unwrap_or(&"".to_string())
     .clone();
@@ -51,15 +51,14 @@ pub enum RunCommand {
     Live,
 }
 
-impl std::string::ToString for RunCommand {
-    fn to_string(&self) -> String {
-        match self {
+impl std::fmt::Display for RunCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
             Self::Init => "init",
             Self::Build => "build",
             Self::Clean => "clean",
             Self::Live => "live",
-        }
-        .to_string()
+        })
     }
 }
 
@@ -74,7 +73,6 @@ pub struct RunContext {
 impl Drop for RunContext {
     fn drop(&mut self) {
         if self.success {
-        } else {
         }
     }
 }
@@ -112,7 +110,7 @@ fn cli(args: Vec<String>) -> RunContext {
         commands
             .iter()
             .for_each(|i| eprint!("`{}` ", i.to_string().yellow()));
-        eprint!(".\n");
+        eprintln!(".");
         help_and_exit();
     }
 
@@ -126,17 +124,17 @@ fn cli(args: Vec<String>) -> RunContext {
         project_dirs
             .iter()
             .for_each(|i| eprint!("`{}` ", i.yellow()));
-        eprint!(".\n");
+        eprintln!(".");
         help_and_exit();
     }
 
-    ctx.command = *commands.get(0).unwrap();
-    ctx.project_dir = project_dirs.get(0).unwrap().clone();
+    ctx.command = *commands.first().unwrap();
+    ctx.project_dir = project_dirs.first().unwrap().clone();
     ctx
 }
 
 fn io_error(str: &str, err: io::Error) -> ! {
-    eprintln!("{} {} {}", "Make Error:".red().bold(), str, err.to_string());
+    eprintln!("{} {} {}", "Make Error:".red().bold(), str, err);
     std::process::exit(1);
 }
 
@@ -165,7 +163,7 @@ fn run(ctx: &RunContext) {
     match ctx.command {
         RunCommand::Init => cmd_init(&ctx.project_dir),
         RunCommand::Clean => cmd_clean(&ctx.project_dir),
-        RunCommand::Build => cmd_build(&ctx, &ctx.project_dir),
+        RunCommand::Build => cmd_build(ctx, &ctx.project_dir),
         _ => unimplemented!(),
     }
 
@@ -177,7 +175,7 @@ fn run(ctx: &RunContext) {
             );
 }
 
-fn cmd_init(pdir: &String) {
+fn cmd_init(pdir: &str) {
     let creates = ["src/", "static/", "dist/", "dist/static/"];
     for create in creates {
         fs::create_dir(create).unwrap_or_else(|e| {
@@ -196,7 +194,7 @@ fn cmd_init(pdir: &String) {
     }
 }
 
-fn cmd_build(run_ctx: &RunContext, pdir: &String) {
+fn cmd_build(run_ctx: &RunContext, pdir: &str) {
     let mut build_ctx = make::load_build().unwrap_or_else(|e| {
         io_error(
             format!(
@@ -208,7 +206,7 @@ fn cmd_build(run_ctx: &RunContext, pdir: &String) {
             e,
         );
     });
-    make::execute_build(&run_ctx, &mut build_ctx).unwrap_or_else(|e| {
+    make::execute_build(run_ctx, &mut build_ctx).unwrap_or_else(|e| {
         io_error(
             format!(
                 "Failed to execute build on `{}` at `{}`",
@@ -221,12 +219,16 @@ fn cmd_build(run_ctx: &RunContext, pdir: &String) {
     });
 }
 
-fn cmd_clean(pdir: &String) {
+fn cmd_clean(pdir: &str) {
     enum FileType {
         Dir,
         File,
     } //  Let's be organized
-    let cleans = [(FileType::Dir, "dist/"), (FileType::File, "osml.cache")];
+    let cleans = [
+        (FileType::Dir, "dist/"),
+        (FileType::File, "osml.cache"),
+        (FileType::File, make::PAGES_NAME),
+    ];
     let mut errors = Vec::new();
     cleans.into_iter().for_each(|(ct, c)| match ct {
         FileType::Dir => {
@@ -1,5 +1,6 @@
 use super::RunContext;
 use colored::Colorize;
+use libosml::Metadata;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -7,17 +8,31 @@ use std::io;
 use std::path::PathBuf;
 use std::time::SystemTime;
 
-const CONFIG_NAME: &'static str = "osml.ron";
-const CACHE_NAME: &'static str = "osml.cache";
+const CONFIG_NAME: &str = "osml.ron";
+const CACHE_NAME: &str = "osml.cache";
+pub const PAGES_NAME: &str = "osml.pages";
+
+//  Included files along with when they were last modified.
+type Includes = Vec<(String, SystemTime)>;
 
 pub struct BuildContext {
     cache: BuildCache,
     config: BuildConfig,
+    rebuild_all: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 struct LoadBuildConfig {
     excluded: Vec<String>,
+    //  Page layout for every source, see `osmlc --template`.
+    #[serde(default)]
+    layout: Option<String>,
+    //  Extends or overrides which block names become which elements, see `osmlc --element`.
+    #[serde(default)]
+    elements: HashMap<String, String>,
+    //  Values for `{{name}}` in every source, see `osmlc --define`.
+    #[serde(default)]
+    variables: HashMap<String, String>,
 }
 
 impl LoadBuildConfig {
@@ -36,6 +51,16 @@ impl LoadBuildConfig {
                     .to_string(),
             );
         }
+        let layout = self.layout.map(|layout| {
+            fs::canonicalize(&layout)
+                .unwrap_or_else(|e| {
+                    errors.push((layout, e));
+                    PathBuf::new()
+                })
+                .to_str()
+                .unwrap()
+                .to_string()
+        });
         if !errors.is_empty() {
             eprint!(
                 "{} Could not open the following files in `osml.ron`: ",
@@ -44,10 +69,19 @@ impl LoadBuildConfig {
             errors
                 .iter()
                 .for_each(|(f, e)| eprint!("\n`{}`: \"{}\"", f.blue(), e));
-            eprint!("\n");
+            eprintln!();
             std::process::exit(1);
         }
-        BuildConfig { excluded }
+        let mut elements: Vec<(String, String)> = self.elements.into_iter().collect();
+        elements.sort();
+        let mut variables: Vec<(String, String)> = self.variables.into_iter().collect();
+        variables.sort();
+        BuildConfig {
+            excluded,
+            layout,
+            elements,
+            variables,
+        }
     }
 }
 
@@ -55,6 +89,11 @@ impl LoadBuildConfig {
 //  Includes both src/ and static/ files
 struct BuildConfig {
     excluded: Vec<String>,
+    layout: Option<String>,
+    //  Sorted by name.
+    elements: Vec<(String, String)>,
+    //  Sorted by name.
+    variables: Vec<(String, String)>,
 }
 
 //  Source file names are stripped of .osml and relative to src/.
@@ -62,6 +101,29 @@ struct BuildConfig {
 #[derive(Serialize, Deserialize)]
 struct BuildCache {
     sources: HashMap<String, SystemTime>,
+    //  Every source is rebuilt when the layout changes.
+    #[serde(default)]
+    layout: Option<SystemTime>,
+    //  Every source is rebuilt when the elements or variables change as well.
+    #[serde(default)]
+    elements: Vec<(String, String)>,
+    #[serde(default)]
+    variables: Vec<(String, String)>,
+    //  Same keys as `sources`.
+    #[serde(default)]
+    meta: HashMap<String, Metadata>,
+    //  Same keys as `sources`.
+    //  A source is rebuilt whenever one of the files it includes changes.
+    #[serde(default)]
+    includes: HashMap<String, Includes>,
+}
+
+//  Every page and its metadata, written to `osml.pages` for listings, feeds and such.
+#[derive(Serialize)]
+struct Page<'a> {
+    //  Relative to dist/.
+    path: String,
+    meta: &'a Metadata,
 }
 
 impl Drop for BuildCache {
@@ -73,9 +135,9 @@ impl Drop for BuildCache {
 
 pub fn check_create_file(file: &String) {
     let splits: Vec<&str> = file.split('/').collect();
-    let name = splits.get(splits.len() - 1).unwrap();
+    let name = splits.last().unwrap();
     let mut dir = file.clone();
-    (0..name.len()).into_iter().for_each(|_| {
+    (0..name.len()).for_each(|_| {
         dir.pop();
     });
     let _ = fs::create_dir_all(dir);
@@ -93,6 +155,9 @@ pub fn load_build() -> io::Result<BuildContext> {
         .unwrap_or_else(|_| {
             let s = ron::to_string(&LoadBuildConfig {
                 excluded: Vec::new(),
+                layout: None,
+                elements: HashMap::new(),
+                variables: HashMap::new(),
             })
             .unwrap();
             fs::write(CONFIG_NAME, &s)?;
@@ -114,12 +179,21 @@ pub fn load_build() -> io::Result<BuildContext> {
             std::process::exit(1);
         })
         .into_config();
-    Ok(BuildContext { cache, config })
+    Ok(BuildContext {
+        cache,
+        config,
+        rebuild_all: false,
+    })
 }
 
 fn clean_cache() -> io::Result<(BuildCache, String)> {
     let cache = BuildCache {
         sources: HashMap::new(),
+        layout: None,
+        elements: Vec::new(),
+        variables: Vec::new(),
+        meta: HashMap::new(),
+        includes: HashMap::new(),
     };
     let s = ron::to_string(&cache).unwrap();
     fs::write(CACHE_NAME, &s)?;
@@ -127,12 +201,31 @@ fn clean_cache() -> io::Result<(BuildCache, String)> {
 }
 
 pub fn execute_build(run_ctx: &RunContext, build_ctx: &mut BuildContext) -> io::Result<()> {
+    let layout_modify = match &build_ctx.config.layout {
+        Some(layout) => Some(fs::metadata(layout)?.modified()?),
+        None => None,
+    };
+    if layout_modify != build_ctx.cache.layout {
+        build_ctx.rebuild_all = true;
+        build_ctx.cache.layout = layout_modify;
+    }
+    if build_ctx.config.elements != build_ctx.cache.elements {
+        build_ctx.rebuild_all = true;
+        build_ctx.cache.elements = build_ctx.config.elements.clone();
+    }
+    if build_ctx.config.variables != build_ctx.cache.variables {
+        build_ctx.rebuild_all = true;
+        build_ctx.cache.variables = build_ctx.config.variables.clone();
+    }
     let sources = list_sources()?;
-    for source in sources {
-        if let Some((name, time)) = compile_source(run_ctx, build_ctx, &source) {
-            build_ctx.cache.sources.insert(name, time);
+    for source in sources.iter() {
+        if let Some((name, time, meta, includes)) = compile_source(run_ctx, build_ctx, source) {
+            build_ctx.cache.sources.insert(name.clone(), time);
+            build_ctx.cache.meta.insert(name.clone(), meta);
+            build_ctx.cache.includes.insert(name, includes);
         }
     }
+    write_pages(build_ctx, &sources)?;
     let statics = list_statics()?;
     let remove_statics = list_remove_statics(&statics)?;
     for remove_static in remove_statics.iter() {
@@ -188,12 +281,12 @@ fn list_statics() -> io::Result<Vec<String>> {
     list_statics_anywhere("./static/")
 }
 
-fn list_remove_statics(statics_list: &Vec<String>) -> io::Result<Vec<String>> {
+fn list_remove_statics(statics_list: &[String]) -> io::Result<Vec<String>> {
     let built = list_statics_anywhere("./dist/static/")?;
     Ok(built
         .into_iter()
         .map(|mut file| {
-            (0..=4).into_iter().for_each(|_| {
+            (0..=4).for_each(|_| {
                 file.remove(0);
             });
             file
@@ -240,47 +333,96 @@ fn compile_source(
     run_ctx: &RunContext,
     build_ctx: &BuildContext,
     src: &String,
-) -> Option<(String, SystemTime)> {
-    let should_compile_res = should_compile_source(build_ctx, src);
-    if let Some(_) = should_compile_res {
-        let mut cmd = std::process::Command::new("./osmlc");
-        let src_name = ("src/".to_string() + src + ".osml").to_string();
-        let dst_name = ("dist/".to_string() + src + ".html").to_string();
-        check_create_file(&dst_name);
-        cmd.args([src_name.as_str(), "-o", dst_name.as_str(), "-c"]);
-        if run_ctx.lame {
-            cmd.arg("-l");
-        }
-        if run_ctx.dryrun {
-            cmd.arg("-d");
+) -> Option<(String, SystemTime, Metadata, Includes)> {
+    let (name, time) = should_compile_source(build_ctx, src)?;
+    let mut cmd = std::process::Command::new("./osmlc");
+    let src_name = ("src/".to_string() + src + ".osml").to_string();
+    let dst_name = ("dist/".to_string() + src + ".html").to_string();
+    check_create_file(&dst_name);
+    cmd.args([src_name.as_str(), "-o", dst_name.as_str(), "-c", "-m"]);
+    if let Some(layout) = &build_ctx.config.layout {
+        cmd.args(["--template", layout.as_str()]);
+    }
+    for (name, element) in &build_ctx.config.elements {
+        cmd.args(["-e", &format!("{}={}", name, element)]);
+    }
+    for (name, value) in &build_ctx.config.variables {
+        cmd.args(["-D", &format!("{}={}", name, value)]);
+    }
+    if run_ctx.lame {
+        cmd.arg("-l");
+    }
+    if run_ctx.dryrun {
+        cmd.arg("-d");
+    }
+    let out = cmd.output();
+    if out.is_err() {
+        eprintln!("{} Could not execute osmlc", "Make Error:".red().bold());
+        std::process::exit(1);
+    }
+    let out = out.unwrap();
+    //  Warnings still end up on stderr, only the exit status tells them apart from errors.
+    if !out.status.success() {
+        eprintln!(
+            "{} {} --> {}",
+            "Error:".red().bold(),
+            src_name.bold(),
+            dst_name.bold(),
+        );
+        for b in out.stderr {
+            eprint!("{}", b as char)
         }
-        let out = cmd.output();
-        if let Err(_) = out {
-            eprintln!("{} Could not execute osmlc", "Make Error:".red().bold());
-            std::process::exit(1);
+        std::process::exit(1);
+    } else {
+        eprintln!(
+            "{} {} --> {}",
+            "OK:".green().bold(),
+            src_name.bold(),
+            dst_name.bold(),
+        );
+        for b in out.stderr {
+            eprint!("{}", b as char)
         }
-        let out = out.unwrap();
-        if !out.stderr.is_empty() {
+    }
+    let (meta, includes): (Metadata, Vec<String>) =
+        ron::from_str(String::from_utf8_lossy(&out.stdout).trim()).unwrap_or_else(|e| {
             eprintln!(
-                "{} {} --> {}",
-                "Error:".red().bold(),
-                src_name.bold(),
-                dst_name.bold(),
+                "{} Could not read the metadata of `{}` {}",
+                "Make Error:".red().bold(),
+                src_name.blue(),
+                e
             );
-            for b in out.stderr {
-                eprint!("{}", b as char)
-            }
             std::process::exit(1);
-        } else {
-            eprintln!(
-                "{} {} --> {}",
-                "OK:".green().bold(),
-                src_name.bold(),
-                dst_name.bold(),
-            );
-        }
-    }
-    should_compile_res
+        });
+    let includes = includes
+        .into_iter()
+        .filter_map(|include| {
+            let modify = fs::metadata(&include).ok()?.modified().ok()?;
+            Some((include, modify))
+        })
+        .collect();
+    Some((name, time, meta, includes))
+}
+
+//  Newest first, pages without a date go last.
+fn write_pages(build_ctx: &BuildContext, sources: &[String]) -> io::Result<()> {
+    let mut pages: Vec<Page> = sources
+        .iter()
+        .filter_map(|src| {
+            Some(Page {
+                path: src.clone() + ".html",
+                meta: build_ctx.cache.meta.get(src)?,
+            })
+        })
+        .collect();
+    pages.sort_by(|a, b| {
+        b.meta
+            .date
+            .cmp(&a.meta.date)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    let s = ron::ser::to_string_pretty(&pages, ron::ser::PrettyConfig::default()).unwrap();
+    fs::write(PAGES_NAME, s)
 }
 
 //  This doesn't need to be run if the file already exists.
@@ -323,8 +465,18 @@ fn should_compile_source(ctx: &BuildContext, src: &String) -> Option<(String, Sy
         }
     }
     let modify = metadata_res.unwrap().modified().unwrap();
+    if ctx.rebuild_all {
+        return Some((src.clone(), modify));
+    }
     if let Some(last_modify) = ctx.cache.sources.get(src) {
-        if last_modify == &modify {
+        let includes_changed = ctx.cache.includes.get(src).is_some_and(|includes| {
+            includes.iter().any(|(include, last_modify)| {
+                fs::metadata(include)
+                    .and_then(|m| m.modified())
+                    .map_or(true, |modify| &modify != last_modify)
+            })
+        });
+        if last_modify == &modify && !includes_changed {
             None?;
         }
     }